@@ -0,0 +1,481 @@
+pub mod sinks;
+
+use crate::config::{AlertCondition, AlertRule};
+use crate::html::ParseError;
+use crate::{PriceBatch, PriceResult};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::time::{Duration, SystemTime};
+
+///Seconds between two alerts of the same rule if the rule has no `cooldown`
+const DEFAULT_COOLDOWN: u64 = 300;
+
+///An alert raised by a rule
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Alert {
+    ///The name of the rule which fired
+    pub rule: String,
+    pub symbol: String,
+    ///Human readable description of what happened
+    pub message: String,
+    ///The value which met the condition, e.g. the price or the percentage moved
+    pub value: Option<f64>,
+    pub timestamp: SystemTime,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time: DateTime<Local> = self.timestamp.into();
+        write!(
+            f,
+            "{} [{}] {}",
+            time.format("%Y-%m-%d %H:%M:%S"),
+            self.rule,
+            self.message
+        )
+    }
+}
+
+///A place alerts are delivered to. Every sink added to the `AlertEngine` receives every alert.
+pub trait AlertSink: Send {
+    ///Returns a human readable name of the sink, e.g. "stderr"
+    fn name(&self) -> String;
+    ///Delivers `alert`
+    /// # Errors
+    /// If the alert could not be delivered an error is returned
+    fn send(&self, alert: &Alert) -> io::Result<()>;
+}
+
+///Prints the alerts to stderr, so they do not mix with prices printed to stdout
+pub struct StderrSink;
+
+impl AlertSink for StderrSink {
+    fn name(&self) -> String {
+        String::from("stderr")
+    }
+    fn send(&self, alert: &Alert) -> io::Result<()> {
+        eprintln!("ALERT {}", alert);
+        Ok(())
+    }
+}
+
+///Returns the name of `rule`, or a description of its condition if it has none
+fn rule_name(rule: &AlertRule) -> String {
+    if let Some(name) = &rule.name {
+        return name.clone();
+    }
+    match &rule.condition {
+        AlertCondition::Above { price } => format!("{} above {}", rule.symbol, price),
+        AlertCondition::Below { price } => format!("{} below {}", rule.symbol, price),
+        AlertCondition::Move { percent, window } => {
+            format!("{} moves {}% in {}s", rule.symbol, percent, window)
+        }
+        AlertCondition::ChangeAbove { percent } => {
+            format!("{} 24h change above {}%", rule.symbol, percent)
+        }
+        AlertCondition::ChangeBelow { percent } => {
+            format!("{} 24h change below {}%", rule.symbol, percent)
+        }
+        AlertCondition::Depeg { low, high } => {
+            format!("{} outside {}-{}", rule.symbol, low, high)
+        }
+    }
+}
+
+///Returns the alert for `symbol` which could not be scraped because of `error`
+fn failure_alert(symbol: &str, error: &ParseError, now: SystemTime) -> Alert {
+    let (rule, message) = match error {
+        ParseError::Transport {
+            status: Some(404), ..
+        }
+        | ParseError::Transport {
+            status: Some(410), ..
+        } => (
+            "symbol not found",
+            format!(
+                "{} does not exist on CoinMarketCap anymore: {}",
+                symbol, error
+            ),
+        ),
+        _ => (
+            "scrape failed",
+            format!("Could not get the price of {}: {}", symbol, error),
+        ),
+    };
+    Alert {
+        rule: String::from(rule),
+        symbol: String::from(symbol),
+        message,
+        value: None,
+        timestamp: now,
+    }
+}
+
+///Whether a rule is beyond its bound and when it fired the last time
+#[derive(Default)]
+struct RuleState {
+    active: bool,
+    last_fired: Option<SystemTime>,
+}
+
+///Checks the `AlertRule`s of the config against fetched prices and delivers the alerts to its sinks.
+/// A rule fires when its value crosses the bound and fires again only after the value moved back by more
+/// than `hysteresis` and `cooldown` seconds passed, so a price hovering at a bound does not raise an alert
+/// on every fetch.
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    states: Vec<RuleState>,
+    ///Prices of the symbols with `move` rules, oldest first
+    history: HashMap<String, VecDeque<(SystemTime, f64)>>,
+    sinks: Vec<Box<dyn AlertSink>>,
+    ///Whether symbols which could not be scraped raise alerts
+    notify_failures: bool,
+    ///The failure state of every symbol which failed since the engine was created
+    failures: HashMap<String, RuleState>,
+}
+
+impl AlertEngine {
+    ///Returns a new `AlertEngine` checking `rules` without any sink
+    pub fn new(rules: Vec<AlertRule>) -> AlertEngine {
+        let states = rules.iter().map(|_| RuleState::default()).collect();
+        AlertEngine {
+            rules,
+            states,
+            history: HashMap::new(),
+            sinks: Vec::new(),
+            notify_failures: false,
+            failures: HashMap::new(),
+        }
+    }
+    ///Sets whether symbols which could not be scraped or do not exist anymore raise alerts in `check_batch`
+    pub fn set_notify_failures(&mut self, notify_failures: bool) {
+        self.notify_failures = notify_failures;
+    }
+    ///Adds `sink` to the sinks every alert is delivered to
+    pub fn add_sink(&mut self, sink: Box<dyn AlertSink>) {
+        self.sinks.push(sink);
+    }
+    ///Returns true if there are no rules to be checked and failures do not raise alerts
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && !self.notify_failures
+    }
+
+    ///Checks all rules against `prices` fetched at `now`, delivers the alerts which fired to all sinks and
+    /// returns them. Errors of the sinks are printed to stderr.
+    pub fn check(&mut self, prices: &[&PriceResult], now: SystemTime) -> Vec<Alert> {
+        let alerts = self.evaluate(prices, now);
+        self.deliver(&alerts);
+        alerts
+    }
+
+    ///Like `check`, but also raises alerts for the symbols of `batch` which could not be scraped if
+    /// failures are notified. A symbol raises a failure alert again only after it was scraped successfully
+    /// and the default cooldown passed.
+    pub fn check_batch(&mut self, batch: &PriceBatch, now: SystemTime) -> Vec<Alert> {
+        let prices: Vec<_> = batch.prices().collect();
+        let mut alerts = self.evaluate(&prices, now);
+        if self.notify_failures {
+            for price in &prices {
+                if let Some(state) = self.failures.get_mut(&price.symbol) {
+                    state.active = false;
+                }
+            }
+            for (symbol, error) in batch.errors() {
+                let state = self.failures.entry(String::from(symbol)).or_default();
+                let cooled_down = match state.last_fired {
+                    Some(t) => {
+                        now.duration_since(t).unwrap_or_default()
+                            >= Duration::from_secs(DEFAULT_COOLDOWN)
+                    }
+                    None => true,
+                };
+                if !state.active && cooled_down {
+                    state.active = true;
+                    state.last_fired = Some(now);
+                    alerts.push(failure_alert(symbol, error, now));
+                }
+            }
+        }
+        self.deliver(&alerts);
+        alerts
+    }
+
+    ///Delivers `alerts` to all sinks
+    fn deliver(&self, alerts: &[Alert]) {
+        for alert in alerts {
+            for sink in &self.sinks {
+                if let Err(e) = sink.send(alert) {
+                    eprintln!("Could not deliver the alert via {}: {}", sink.name(), e);
+                }
+            }
+        }
+    }
+
+    ///Updates the state of all rules with `prices` and returns the alerts which fired
+    fn evaluate(&mut self, prices: &[&PriceResult], now: SystemTime) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            let price = match prices.iter().find(|p| p.symbol == rule.symbol) {
+                Some(p) => p,
+                None => continue,
+            };
+            let history = self.history.get(&rule.symbol);
+            let (excess, value, message) = match measure(&rule.condition, price, history, now) {
+                Some(m) => m,
+                None => continue,
+            };
+            if excess > 0.0 {
+                let cooldown = Duration::from_secs(rule.cooldown.unwrap_or(DEFAULT_COOLDOWN));
+                let cooled_down = match state.last_fired {
+                    Some(t) => now.duration_since(t).unwrap_or_default() >= cooldown,
+                    None => true,
+                };
+                if !state.active && cooled_down {
+                    state.active = true;
+                    state.last_fired = Some(now);
+                    alerts.push(Alert {
+                        rule: rule_name(rule),
+                        symbol: rule.symbol.clone(),
+                        message,
+                        value: Some(value),
+                        timestamp: now,
+                    });
+                }
+            } else if excess <= -rule.hysteresis.unwrap_or(0.0) {
+                state.active = false;
+            }
+        }
+        self.record(prices, now);
+        alerts
+    }
+
+    ///Adds `prices` to the history of the symbols with `move` rules and drops prices older than the longest
+    /// window
+    fn record(&mut self, prices: &[&PriceResult], now: SystemTime) {
+        for price in prices {
+            let window = self
+                .rules
+                .iter()
+                .filter(|r| r.symbol == price.symbol)
+                .filter_map(|r| match r.condition {
+                    AlertCondition::Move { window, .. } => Some(window),
+                    _ => None,
+                })
+                .max();
+            if let Some(window) = window {
+                let history = self.history.entry(price.symbol.clone()).or_default();
+                history.push_back((now, price.price.to_f64()));
+                while let Some((t, _)) = history.front() {
+                    if now.duration_since(*t).unwrap_or_default() > Duration::from_secs(window) {
+                        history.pop_front();
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+///Returns how far `price` is beyond the bound of `condition` (negative if within), the value compared with
+/// the bound and a message describing it. Returns None if the condition can not be checked yet, i.e. a
+/// `move` without earlier prices in its window.
+fn measure(
+    condition: &AlertCondition,
+    price: &PriceResult,
+    history: Option<&VecDeque<(SystemTime, f64)>>,
+    now: SystemTime,
+) -> Option<(f64, f64, String)> {
+    let symbol = &price.symbol;
+    //the thresholds of the rules are plain numbers
+    let value = price.price.to_f64();
+    match condition {
+        AlertCondition::Above { price: bound } => Some((
+            value - bound,
+            value,
+            format!("{} is above {} at {}", symbol, bound, price.price),
+        )),
+        AlertCondition::Below { price: bound } => Some((
+            bound - value,
+            value,
+            format!("{} is below {} at {}", symbol, bound, price.price),
+        )),
+        AlertCondition::Move { percent, window } => {
+            let window = Duration::from_secs(*window);
+            let (_, first) = history?
+                .iter()
+                .find(|(t, _)| now.duration_since(*t).unwrap_or_default() <= window)?;
+            if *first == 0.0 {
+                return None;
+            }
+            let moved = (value - first) / first * 100.0;
+            Some((
+                moved.abs() - percent,
+                moved,
+                format!(
+                    "{} moved {:+.2}% within {}s to {}",
+                    symbol,
+                    moved,
+                    window.as_secs(),
+                    price.price
+                ),
+            ))
+        }
+        AlertCondition::ChangeAbove { percent } => Some((
+            price.change - percent,
+            price.change,
+            format!(
+                "24h change of {} is {}% (above {}%)",
+                symbol, price.change, percent
+            ),
+        )),
+        AlertCondition::ChangeBelow { percent } => Some((
+            percent - price.change,
+            price.change,
+            format!(
+                "24h change of {} is {}% (below {}%)",
+                symbol, price.change, percent
+            ),
+        )),
+        AlertCondition::Depeg { low, high } => Some((
+            (low - value).max(value - high),
+            value,
+            format!("{} left {}-{} at {}", symbol, low, high, price.price),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Alert, AlertEngine, AlertSink};
+    use crate::config::{AlertCondition, AlertRule};
+    use crate::html::ParseError;
+    use crate::money::Decimal;
+    use crate::{PriceBatch, PriceResult};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    struct Collect(Arc<Mutex<Vec<Alert>>>);
+    impl AlertSink for Collect {
+        fn name(&self) -> String {
+            String::from("collect")
+        }
+        fn send(&self, alert: &Alert) -> io::Result<()> {
+            self.0.lock().unwrap().push(alert.clone());
+            Ok(())
+        }
+    }
+
+    fn rule(symbol: &str, condition: AlertCondition) -> AlertRule {
+        AlertRule {
+            name: None,
+            symbol: String::from(symbol),
+            condition,
+            hysteresis: None,
+            cooldown: Some(0),
+        }
+    }
+
+    fn price(symbol: &str, price: f64, change: f64) -> PriceResult {
+        PriceResult {
+            symbol: String::from(symbol),
+            price: Decimal::from_f64(price).unwrap(),
+            change,
+            currency: String::from("USD"),
+            ..Default::default()
+        }
+    }
+
+    fn t(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_hysteresis_and_cooldown() {
+        let mut above = rule("bitcoin", AlertCondition::Above { price: 60000.0 });
+        above.hysteresis = Some(500.0);
+        let mut engine = AlertEngine::new(vec![above]);
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        engine.add_sink(Box::new(Collect(Arc::clone(&alerts))));
+        let mut fired = |p: f64, at: u64| engine.check(&[&price("bitcoin", p, 0.0)], t(at)).len();
+        assert_eq!(fired(59000.0, 0), 0);
+        assert_eq!(fired(60100.0, 10), 1);
+        //hovering around the bound within the hysteresis does not fire again
+        assert_eq!(fired(59800.0, 20), 0);
+        assert_eq!(fired(60050.0, 30), 0);
+        //moving back far enough re-arms the rule
+        assert_eq!(fired(59400.0, 40), 0);
+        assert_eq!(fired(60200.0, 50), 1);
+        assert_eq!(alerts.lock().unwrap()[0].rule, "bitcoin above 60000");
+
+        let mut below = rule("bitcoin", AlertCondition::Below { price: 40000.0 });
+        below.cooldown = Some(60);
+        let mut engine = AlertEngine::new(vec![below]);
+        let mut fired = |p: f64, at: u64| engine.check(&[&price("bitcoin", p, 0.0)], t(at)).len();
+        assert_eq!(fired(39000.0, 0), 1);
+        assert_eq!(fired(41000.0, 10), 0);
+        //re-armed but still cooling down
+        assert_eq!(fired(39000.0, 20), 0);
+        assert_eq!(fired(39000.0, 60), 1);
+    }
+
+    #[test]
+    fn test_move_change_and_depeg() {
+        let mut engine = AlertEngine::new(vec![
+            rule(
+                "bitcoin",
+                AlertCondition::Move {
+                    percent: 5.0,
+                    window: 3600,
+                },
+            ),
+            rule("bitcoin", AlertCondition::ChangeBelow { percent: -10.0 }),
+            rule(
+                "tether",
+                AlertCondition::Depeg {
+                    low: 0.99,
+                    high: 1.01,
+                },
+            ),
+        ]);
+        assert!(engine
+            .check(&[&price("bitcoin", 50000.0, -2.0)], t(0))
+            .is_empty());
+        assert!(engine
+            .check(&[&price("bitcoin", 52000.0, -2.0)], t(1800))
+            .is_empty());
+        let alerts = engine.check(&[&price("bitcoin", 53000.0, -12.0)], t(3000));
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].value, Some(6.0));
+        //the price of t(0) left the window, so 51000 is compared with 52000
+        assert!(engine
+            .check(&[&price("bitcoin", 51000.0, -2.0)], t(4000))
+            .is_empty());
+        let alerts = engine.check(&[&price("tether", 0.985, 0.0)], t(4000));
+        assert_eq!(alerts[0].message, "tether left 0.99-1.01 at 0.985");
+    }
+
+    #[test]
+    fn test_failures() {
+        let mut engine = AlertEngine::new(Vec::new());
+        assert!(engine.is_empty());
+        engine.set_notify_failures(true);
+        let not_found = || PriceBatch {
+            results: vec![(
+                String::from("bitcoin-old"),
+                Err(ParseError::Transport {
+                    url: String::from("https://coinmarketcap.com/currencies/bitcoin-old/"),
+                    status: Some(404),
+                    details: String::new(),
+                }),
+            )],
+        };
+        let alerts = engine.check_batch(&not_found(), t(0));
+        assert_eq!(alerts[0].rule, "symbol not found");
+        assert!(engine.check_batch(&not_found(), t(1000)).is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+use super::{Alert, AlertSink};
+use crate::config::{NotifyConfig, NotifySink};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+///The body of a webhook without a template
+const DEFAULT_TEMPLATE: &str = r#"{"rule": {{rule}}, "symbol": {{symbol}}, "message": {{message}}, "value": {{value}}, "timestamp": {{timestamp}}}"#;
+///Seconds a webhook may take to answer
+const WEBHOOK_TIMEOUT: u64 = 10;
+
+///Returns the values of the placeholders of `alert` as JSON, e.g. `("symbol", "\"bitcoin\"")`
+fn placeholders(alert: &Alert) -> Vec<(&'static str, String)> {
+    let json = |s: &str| serde_json::Value::from(s).to_string();
+    let timestamp: DateTime<Utc> = alert.timestamp.into();
+    vec![
+        ("rule", json(&alert.rule)),
+        ("symbol", json(&alert.symbol)),
+        ("message", json(&alert.message)),
+        (
+            "value",
+            match alert.value {
+                Some(v) => serde_json::Value::from(v).to_string(),
+                None => String::from("null"),
+            },
+        ),
+        (
+            "timestamp",
+            json(&timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        ),
+    ]
+}
+
+///Returns `template` with the placeholders replaced by the values of `alert`
+/// # Errors
+/// If the result is not valid JSON an error is returned
+pub fn render_template(template: &str, alert: &Alert) -> io::Result<String> {
+    let mut body = String::from(template);
+    for (name, value) in placeholders(alert) {
+        body = body.replace(&format!("{{{{{}}}}}", name), &value);
+    }
+    match serde_json::from_str::<serde_json::Value>(&body) {
+        Ok(_) => Ok(body),
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The webhook template is not valid JSON: {}", e),
+        )),
+    }
+}
+
+///POSTs the alerts as JSON to a URL, e.g. the incoming webhook of a chat
+pub struct WebhookSink {
+    url: String,
+    template: String,
+}
+impl WebhookSink {
+    ///Returns a new `WebhookSink` posting to `url` with the body `template`, see `render_template`
+    pub fn new(url: &str, template: Option<&str>) -> WebhookSink {
+        WebhookSink {
+            url: String::from(url),
+            template: String::from(template.unwrap_or(DEFAULT_TEMPLATE)),
+        }
+    }
+}
+
+impl AlertSink for WebhookSink {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+    fn send(&self, alert: &Alert) -> io::Result<()> {
+        let body = render_template(&self.template, alert)?;
+        let url = self.url.clone();
+        //the blocking client must not run on a thread of the tokio runtime of the scrapper
+        let post = std::thread::spawn(move || -> Result<u16, String> {
+            let client = reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(WEBHOOK_TIMEOUT))
+                .build()
+                .map_err(|e| e.to_string())?;
+            let response = client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .map_err(|e| e.to_string())?;
+            Ok(response.status().as_u16())
+        });
+        match post.join() {
+            Ok(Ok(status)) if status < 300 => Ok(()),
+            Ok(Ok(status)) => Err(io::Error::other(format!(
+                "{} answered with HTTP status {}",
+                self.url, status
+            ))),
+            Ok(Err(e)) => Err(io::Error::other(e)),
+            Err(_) => Err(io::Error::other("The webhook request panicked")),
+        }
+    }
+}
+
+///Runs a shell command for every alert
+pub struct CommandSink {
+    command: String,
+}
+impl CommandSink {
+    ///Returns a new `CommandSink` running `command` with `sh -c` (`cmd /C` on Windows)
+    pub fn new(command: &str) -> CommandSink {
+        CommandSink {
+            command: String::from(command),
+        }
+    }
+}
+
+impl AlertSink for CommandSink {
+    fn name(&self) -> String {
+        self.command.clone()
+    }
+    fn send(&self, alert: &Alert) -> io::Result<()> {
+        let mut command = if cfg!(windows) {
+            let mut c = Command::new("cmd");
+            c.arg("/C");
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.arg("-c");
+            c
+        };
+        command.arg(&self.command);
+        for (name, value) in placeholders(alert) {
+            //the environment gets the plain values, not the JSON ones
+            let value = match serde_json::from_str::<serde_json::Value>(&value) {
+                Ok(serde_json::Value::String(s)) => s,
+                Ok(serde_json::Value::Null) => String::new(),
+                _ => value,
+            };
+            command.env(format!("ALERT_{}", name.to_uppercase()), value);
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            //the command may exit without reading its input
+            let _ = stdin.write_all(render_template(DEFAULT_TEMPLATE, alert)?.as_bytes());
+        }
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "\"{}\" exited with {}",
+                self.command, status
+            )))
+        }
+    }
+}
+
+///Appends the alerts to a log file, one line per alert
+pub struct LogFileSink {
+    path: String,
+}
+impl LogFileSink {
+    ///Returns a new `LogFileSink` appending to the file at `path`. The file is created if it does not exist.
+    pub fn new(path: &str) -> LogFileSink {
+        LogFileSink {
+            path: String::from(path),
+        }
+    }
+}
+
+impl AlertSink for LogFileSink {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+    fn send(&self, alert: &Alert) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", alert)
+    }
+}
+
+///Delivers the alerts with another sink and retries failed deliveries with exponential backoff
+pub struct RetryingSink {
+    sink: Box<dyn AlertSink>,
+    retries: u32,
+    backoff: Duration,
+}
+impl RetryingSink {
+    ///Returns a new `RetryingSink` retrying `retries` times, waiting `backoff` before the first retry and
+    /// twice as long before every further one
+    pub fn new(sink: Box<dyn AlertSink>, retries: u32, backoff: Duration) -> RetryingSink {
+        RetryingSink {
+            sink,
+            retries,
+            backoff,
+        }
+    }
+}
+
+impl AlertSink for RetryingSink {
+    fn name(&self) -> String {
+        self.sink.name()
+    }
+    fn send(&self, alert: &Alert) -> io::Result<()> {
+        let mut wait = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match self.sink.send(alert) {
+                Ok(_) => return Ok(()),
+                //an invalid template does not get better by retrying
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => return Err(e),
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    std::thread::sleep(wait);
+                    wait *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+///Delivers the alerts with another sink on a background thread, so a slow or retrying sink does not block
+/// the fetch which raised the alert. Alerts still queued when the sink is dropped are delivered before the drop
+/// returns.
+pub struct QueuedSink {
+    name: String,
+    sender: Option<Sender<Alert>>,
+    worker: Option<JoinHandle<()>>,
+}
+impl QueuedSink {
+    ///Returns a new `QueuedSink` delivering the alerts with `sink` in the order they were sent
+    pub fn new(sink: Box<dyn AlertSink>) -> QueuedSink {
+        let name = sink.name();
+        let (sender, receiver) = mpsc::channel::<Alert>();
+        let worker = std::thread::spawn(move || {
+            for alert in receiver {
+                if let Err(e) = sink.send(&alert) {
+                    eprintln!("Could not deliver the alert via {}: {}", sink.name(), e);
+                }
+            }
+        });
+        QueuedSink {
+            name,
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+}
+
+impl AlertSink for QueuedSink {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    ///Queues `alert` and returns without waiting for its delivery. Errors of the delivery are printed to stderr.
+    fn send(&self, alert: &Alert) -> io::Result<()> {
+        let sent = match &self.sender {
+            Some(s) => s.send(alert.clone()).is_ok(),
+            None => false,
+        };
+        if sent {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the delivery thread has stopped",
+            ))
+        }
+    }
+}
+
+impl Drop for QueuedSink {
+    fn drop(&mut self) {
+        //closing the channel ends the worker once the queue is empty
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+///Returns the sink described by `config`, retrying failed deliveries on a background thread
+pub fn from_config(config: &NotifyConfig) -> Box<dyn AlertSink> {
+    let sink: Box<dyn AlertSink> = match &config.sink {
+        NotifySink::Webhook { url, template } => {
+            Box::new(WebhookSink::new(url, template.as_deref()))
+        }
+        NotifySink::Command { command } => Box::new(CommandSink::new(command)),
+        NotifySink::LogFile { path } => Box::new(LogFileSink::new(path)),
+    };
+    Box::new(QueuedSink::new(Box::new(RetryingSink::new(
+        sink,
+        config.retries.unwrap_or(3),
+        Duration::from_secs(config.backoff.unwrap_or(1)),
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_template, CommandSink, LogFileSink, QueuedSink, RetryingSink, WebhookSink};
+    use crate::alert::{Alert, AlertSink};
+    use std::io;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    fn alert() -> Alert {
+        Alert {
+            rule: String::from("bitcoin above 60000"),
+            symbol: String::from("bitcoin"),
+            message: String::from("bitcoin is \"above\" 60000 at 60100"),
+            value: Some(60100.0),
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_614_000_000),
+        }
+    }
+
+    #[test]
+    fn test_template() {
+        assert_eq!(
+            render_template(r#"{"text": {{message}}, "at": {{timestamp}}}"#, &alert()).unwrap(),
+            r#"{"text": "bitcoin is \"above\" 60000 at 60100", "at": "2021-02-22T13:20:00Z"}"#
+        );
+        assert!(render_template("text: {{message}}", &alert()).is_err());
+    }
+
+    #[test]
+    fn test_webhook_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        //answers the first request with an error and records the body of the second one
+        let server = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in ["500 Internal Server Error", "204 No Content"].iter() {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                while !String::from_utf8_lossy(&request).ends_with('}') {
+                    let n = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..n]);
+                }
+                let request = String::from_utf8(request).unwrap();
+                bodies.push(String::from(request.split("\r\n\r\n").nth(1).unwrap()));
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        let sink = RetryingSink::new(
+            Box::new(WebhookSink::new(&url, Some(r#"{"text": {{message}}}"#))),
+            2,
+            Duration::from_millis(10),
+        );
+        sink.send(&alert()).unwrap();
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(
+            bodies[1],
+            r#"{"text": "bitcoin is \"above\" 60000 at 60100"}"#
+        );
+    }
+
+    #[test]
+    fn test_queued_delivery() {
+        struct Slow(Arc<Mutex<Vec<Alert>>>);
+        impl AlertSink for Slow {
+            fn name(&self) -> String {
+                String::from("slow")
+            }
+            fn send(&self, alert: &Alert) -> io::Result<()> {
+                std::thread::sleep(Duration::from_millis(200));
+                self.0.lock().unwrap().push(alert.clone());
+                Ok(())
+            }
+        }
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sink = QueuedSink::new(Box::new(Slow(Arc::clone(&delivered))));
+        let start = Instant::now();
+        sink.send(&alert()).unwrap();
+        sink.send(&alert()).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(200));
+        //dropping the sink waits for the queued alerts
+        drop(sink);
+        assert_eq!(delivered.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_log_file_and_command() {
+        let file = std::env::temp_dir().join("crypto_scrapper_alerts.log");
+        let _ = std::fs::remove_file(&file);
+        let path = file.to_str().unwrap();
+        let sink = LogFileSink::new(path);
+        sink.send(&alert()).unwrap();
+        sink.send(&alert()).unwrap();
+        let log = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(log.lines().count(), 2);
+        assert!(log.ends_with("[bitcoin above 60000] bitcoin is \"above\" 60000 at 60100\n"));
+        if cfg!(unix) {
+            let _ = std::fs::remove_file(&file);
+            let sink = CommandSink::new(&format!("echo \"$ALERT_SYMBOL $ALERT_VALUE\" > {}", path));
+            sink.send(&alert()).unwrap();
+            assert_eq!(std::fs::read_to_string(&file).unwrap(), "bitcoin 60100.0\n");
+            assert!(CommandSink::new("exit 3").send(&alert()).is_err());
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::frontend::{cli_menu, run_dashboard};
+use crypto_scrapper::daemon::Trigger;
+use crypto_scrapper::output::{render_markets, render_prices, OutputFormat};
+use crypto_scrapper::server::serve;
+use crypto_scrapper::spreadsheet::update_workbook;
+use crypto_scrapper::CoinMarketCapScrapper;
+use std::time::{Duration, SystemTime};
+use structopt::StructOpt;
+
+///Scrapes prices, details and markets of crypto currencies from CoinMarketCap
+#[derive(StructOpt, Debug)]
+#[structopt(name = "crypto_scrapper")]
+pub struct Opt {
+    ///The configuration file to be used
+    #[structopt(short, long, default_value = "./config/config.toml")]
+    pub config: String,
+    ///The format of prices and markets: table, json, ndjson, csv or tsv
+    #[structopt(short, long, default_value = "table")]
+    pub format: OutputFormat,
+    ///The command to be executed. If no command is given the interactive menu is started.
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    ///Starts the interactive menu
+    Menu,
+    ///Starts the full-screen dashboard showing the prices of all symbols in the config
+    Dashboard {
+        ///Seconds between two updates of the prices
+        #[structopt(short, long, default_value = "60")]
+        refresh: u64,
+    },
+    ///Prints the prices of the given symbols or of all symbols in the config
+    Prices {
+        ///The symbols to get the prices of. Defaults to the symbols stored in the config.
+        symbols: Vec<String>,
+    },
+    ///Prints the price of a single symbol
+    Price { symbol: String },
+    ///Prints the "What is" section of a symbol
+    Details { symbol: String },
+    ///Prints the markets with the highest volume of a symbol
+    Markets {
+        symbol: String,
+        ///Number of markets to be printed
+        #[structopt(short, long, default_value = "3")]
+        limit: i32,
+    },
+    ///Writes the prices into a sheet of an .xlsx workbook, matching the rows by symbol
+    Export {
+        ///The workbook to be updated. Defaults to `workbook` of the config.
+        #[structopt(short, long)]
+        workbook: Option<String>,
+        ///The sheet holding the prices. Defaults to `workbook_sheet` of the config or "Prices".
+        #[structopt(short, long)]
+        sheet: Option<String>,
+        ///The symbols to be exported. Defaults to the symbols stored in the config.
+        symbols: Vec<String>,
+    },
+    ///Takes snapshots of the prices of all symbols in the config on a schedule until SIGINT or SIGTERM is
+    /// received. The snapshots are recorded in the store of the config.
+    Daemon {
+        ///Seconds between two snapshots. Overrides `schedule` of the config.
+        #[structopt(short, long, conflicts_with = "cron")]
+        interval: Option<u64>,
+        ///Cron expression of the snapshot times, e.g. "*/5 * * * *". Overrides `schedule` of the config.
+        #[structopt(long)]
+        cron: Option<String>,
+    },
+    ///Answers HTTP requests for prices, markets, details and symbols with JSON until SIGINT or SIGTERM is
+    /// received
+    Serve {
+        ///The address the server listens on
+        #[structopt(short, long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+    ///Manages the symbols stored in the config
+    Symbols(SymbolsCommand),
+}
+
+#[derive(StructOpt, Debug)]
+pub enum SymbolsCommand {
+    ///Adds a symbol as it appears in the CoinMarketCap address bar
+    Add { symbol: String },
+    ///Removes a symbol
+    Rm { symbol: String },
+    ///Lists the stored symbols
+    Ls,
+}
+
+///Executes the command of `opt` and returns the exit code of the process.
+/// Results are written to stdout, errors to stderr. If a single value could not be scraped the exit code is 1.
+pub fn run(opt: Opt) -> i32 {
+    let mut scrapper = match CoinMarketCapScrapper::new(String::from(&opt.config)) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!(
+                "{}\nMake sure that you specify an existing configuration file.",
+                e
+            );
+            return 2;
+        }
+    };
+    let rounding = scrapper.cfg.configuration.rounding.clone();
+    match opt.command {
+        None | Some(Command::Menu) => {
+            cli_menu(scrapper);
+            0
+        }
+        Some(Command::Dashboard { refresh }) => {
+            match run_dashboard(&mut scrapper, Duration::from_secs(refresh)) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Could not start the dashboard: {}", e);
+                    2
+                }
+            }
+        }
+        Some(Command::Prices { symbols }) => {
+            let prices = if symbols.is_empty() {
+                scrapper.get_all_prices()
+            } else {
+                scrapper.get_prices(&symbols)
+            };
+            let results: Vec<_> = prices.prices().collect();
+            print!("{}", render_prices(&results, opt.format, rounding.as_ref()));
+            for (s, e) in prices.errors() {
+                eprintln!("Could not get the price of {}: {}", s, e);
+            }
+            if prices.is_complete() {
+                0
+            } else {
+                1
+            }
+        }
+        Some(Command::Price { symbol }) => match scrapper.get_price(&symbol) {
+            Ok(p) => {
+                print!("{}", render_prices(&[&p], opt.format, rounding.as_ref()));
+                0
+            }
+            Err(e) => {
+                eprintln!("Could not get the price of {}: {}", symbol, e);
+                1
+            }
+        },
+        Some(Command::Details { symbol }) => match scrapper.get_details(&symbol) {
+            Ok(d) => {
+                println!("{}", d);
+                0
+            }
+            Err(e) => {
+                eprintln!("Could not get the details of {}: {}", symbol, e);
+                1
+            }
+        },
+        Some(Command::Markets { symbol, limit }) => {
+            match scrapper.get_market_data(&symbol, limit) {
+                Ok(r) => {
+                    print!("{}", render_markets(&r, opt.format, rounding.as_ref()));
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Could not get the markets of {}: {}", symbol, e);
+                    1
+                }
+            }
+        }
+        Some(Command::Export {
+            workbook,
+            sheet,
+            symbols,
+        }) => {
+            let config = &scrapper.cfg.configuration;
+            let workbook = match workbook.or_else(|| config.workbook.clone()) {
+                Some(w) => w,
+                None => {
+                    eprintln!("No workbook given. Use --workbook or set workbook in the config.");
+                    return 2;
+                }
+            };
+            let sheet = sheet
+                .or_else(|| config.workbook_sheet.clone())
+                .unwrap_or_else(|| String::from("Prices"));
+            let prices = if symbols.is_empty() {
+                scrapper.get_all_prices()
+            } else {
+                scrapper.get_prices(&symbols)
+            };
+            for (s, e) in prices.errors() {
+                eprintln!("Could not get the price of {}: {}", s, e);
+            }
+            let rule = rounding.as_ref().and_then(|r| r.spreadsheet.as_ref());
+            let rounded: Vec<_> = prices.prices().map(|p| p.rounded(rule)).collect();
+            let results: Vec<_> = rounded.iter().collect();
+            match update_workbook(&workbook, &sheet, &results, SystemTime::now()) {
+                Ok(_) => {
+                    println!(
+                        "Updated {} prices in {} ({}).",
+                        results.len(),
+                        workbook,
+                        sheet
+                    );
+                    if prices.is_complete() {
+                        0
+                    } else {
+                        1
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            }
+        }
+        Some(Command::Daemon { interval, cron }) => {
+            let trigger = match (interval, cron) {
+                (Some(seconds), _) => Ok(Trigger::Interval(Duration::from_secs(seconds))),
+                (None, Some(expression)) => Trigger::from_cron(&expression),
+                (None, None) => Trigger::from_config(scrapper.cfg.configuration.schedule.as_ref()),
+            };
+            match trigger.and_then(|t| scrapper.run_daemon(&t)) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    2
+                }
+            }
+        }
+        Some(Command::Serve { address }) => match serve(&mut scrapper, &address) {
+            Ok(_) => 0,
+            Err(e) => {
+                eprintln!("{}", e);
+                2
+            }
+        },
+        Some(Command::Symbols(command)) => run_symbols(&mut scrapper, command),
+    }
+}
+
+///Executes `command` on the symbols stored in the config of `scrapper`. Changes are stored when the
+/// config is dropped.
+fn run_symbols(scrapper: &mut CoinMarketCapScrapper, command: SymbolsCommand) -> i32 {
+    match command {
+        SymbolsCommand::Add { symbol } => {
+            println!("Added {}.", symbol);
+            scrapper.cfg.add_symbol(symbol);
+            0
+        }
+        SymbolsCommand::Rm { symbol } => {
+            match scrapper.cfg.get_symbols().iter().position(|s| s == &symbol) {
+                Some(i) => {
+                    println!("{} removed.", scrapper.cfg.delete_symbol(i));
+                    0
+                }
+                None => {
+                    eprintln!("{} is not in the config.", symbol);
+                    1
+                }
+            }
+        }
+        SymbolsCommand::Ls => {
+            scrapper
+                .cfg
+                .get_symbols()
+                .iter()
+                .for_each(|s| println!("{}", s));
+            0
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::io;
 use toml;
 
@@ -8,6 +9,114 @@ pub struct Replace {
     pub from: String,
     pub to: String,
 }
+///The way prices, markets and details are extracted from a page
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Extractor {
+    ///Elements are found with `price_regex`, `what_is_regex`, ... and navigated with `ElementRelation`s
+    #[default]
+    Markup,
+    ///Values are read from the `__NEXT_DATA__` JSON embedded in the page
+    NextData,
+    ///Elements are found with the CSS selectors and path expressions stored in `selectors`
+    Selectors,
+}
+
+impl fmt::Display for Extractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Extractor::Markup => "markup",
+            Extractor::NextData => "next_data",
+            Extractor::Selectors => "selectors",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+///The path expressions used by `Extractor::Selectors`. A path expression is a CSS selector followed by optional
+/// relation steps, e.g. `h2[id^="what-is-"] -> parent` (see `html::get_inner_html_from_path`).
+/// In the market expressions `(n)` is replaced by the number of the row, starting at 1.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Selectors {
+    pub price: String,
+    ///Element containing the 24h change, which is matched with `price_percentage_regex`
+    pub price_change: String,
+    ///Elements containing the details text. The first one found is used.
+    pub details: Vec<String>,
+    pub market_source: String,
+    pub market_pair: String,
+    pub market_price: String,
+    pub market_volume: String,
+    pub market_volume_percent: String,
+}
+impl Default for Selectors {
+    fn default() -> Self {
+        let row = r#"table[class*="currencies-markets_"] > tbody > tr:nth-child(n)"#;
+        Selectors {
+            price: String::from(r#"div[class*="priceTitle__"] > div"#),
+            price_change: String::from(r#"div[class*="priceTitle__"] > div -> sibling(0)"#),
+            details: vec![
+                String::from(r#"[id^="what-is-"] -> parent"#),
+                String::from(r#"div[class*="about___"] -> parent"#),
+            ],
+            market_source: format!("{} > td:nth-child(2) > * > * > :nth-child(2) > *", row),
+            market_pair: format!("{} > td:nth-child(3) > * > *", row),
+            market_price: format!("{} > td:nth-child(4)", row),
+            market_volume: format!("{} > td:nth-child(5) > *", row),
+            market_volume_percent: format!("{} > td:nth-child(6) > * > *", row),
+        }
+    }
+}
+///The regular expressions the statistics next to the price are extracted with by `Extractor::Markup` and
+/// `Extractor::Selectors`. Each pattern is matched against the currency page and its first capture group holds
+/// the value, e.g. `$930,332,625,053` or `18,644,618 BTC`. Statistics whose pattern is empty, does not match or
+/// does not capture a number are left out of the `PriceResult`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct StatisticsPatterns {
+    pub market_cap: String,
+    pub fully_diluted_market_cap: String,
+    pub volume_24h: String,
+    pub rank: String,
+    pub circulating_supply: String,
+    pub total_supply: String,
+    pub max_supply: String,
+    ///Change of the price within 1 hour in percent. The captured number has to carry its sign. The change is not
+    /// shown in the markup of the currency pages, so by default the pattern is empty and the change is taken
+    /// from the quote of the coin in `__NEXT_DATA__`, see `next_data::parse_changes`.
+    pub change_1h: String,
+    ///Change of the price within 7 days in percent, see `change_1h`
+    pub change_7d: String,
+}
+impl Default for StatisticsPatterns {
+    fn default() -> Self {
+        let stat = |label: &str| {
+            format!(
+                r#"(?s)statsLabel___[\w-]+">{}<.*?statsValue___[\w-]+">([^<]*)<"#,
+                label
+            )
+        };
+        let supply = |label: &str| {
+            format!(
+                r#"(?s)statsLabel___[\w-]+">{}<.*?maxSupplyValue___[\w-]+">([^<]*)<"#,
+                label
+            )
+        };
+        StatisticsPatterns {
+            market_cap: stat("Market Cap"),
+            fully_diluted_market_cap: stat("Fully Diluted Market Cap"),
+            volume_24h: stat("Volume"),
+            rank: String::from(r#"namePillPrimary___[\w-]+">Rank #([0-9]+)<"#),
+            circulating_supply: stat("Circulating Supply"),
+            total_supply: supply("Total Supply"),
+            max_supply: supply("Max Supply"),
+            //only part of the quotes in __NEXT_DATA__, which are keyed by the id of the coin
+            change_1h: String::new(),
+            change_7d: String::new(),
+        }
+    }
+}
 ///Structure which holds the configuration details
 #[derive(Serialize, Deserialize)]
 pub struct Config {
@@ -19,8 +128,218 @@ pub struct Config {
     pub title_regex: String,
     pub price_regex: String,
     pub price_percentage_regex: String,
+    ///The way values are extracted from the pages. Defaults to `markup`.
+    #[serde(default)]
+    pub extractor: Extractor,
+    ///Directory of the on-disk page cache. If not set pages are only cached in memory.
+    #[serde(default)]
+    pub cache_directory: Option<String>,
+    ///Maximum number of pages held in the memory cache. If not set the number is not limited.
+    #[serde(default)]
+    pub cache_max_entries: Option<usize>,
+    ///Maximum size of the pages held in the memory cache in bytes. If not set the size is not limited.
+    #[serde(default)]
+    pub cache_max_bytes: Option<usize>,
+    ///Seconds after which a cached price page is loaded again. `0` means never cache. Defaults to 45 seconds.
+    #[serde(default)]
+    pub price_refresh_after: Option<u64>,
+    ///Seconds after which a cached market page is loaded again. `0` means never cache. If not set the page is
+    /// loaded once per run.
+    #[serde(default)]
+    pub market_refresh_after: Option<u64>,
+    ///Seconds after which a cached details page is loaded again. `0` means never cache. If not set the page is
+    /// loaded once per run.
+    #[serde(default)]
+    pub details_refresh_after: Option<u64>,
+    ///File the prices are written to if no clipboard is available (e.g. on a headless machine). If not set
+    /// they are printed to stdout.
+    #[serde(default)]
+    pub clipboard_file: Option<String>,
+    ///The .xlsx workbook updated with the prices of all symbols. If not set the prices are only copied to the
+    /// clipboard.
+    #[serde(default)]
+    pub workbook: Option<String>,
+    ///The sheet of `workbook` holding the prices. Defaults to "Prices".
+    #[serde(default)]
+    pub workbook_sheet: Option<String>,
+    ///SQLite database recording every scraped price and market. If not set nothing is recorded.
+    #[serde(default)]
+    pub store: Option<String>,
+    ///Raise alerts for symbols which could not be scraped or do not exist anymore. Defaults to false.
+    #[serde(default)]
+    pub notify_failures: Option<bool>,
+    ///ISO 4217 code of the currency prices and markets are quoted in, e.g. "CHF". Defaults to "USD".
+    #[serde(default)]
+    pub currency: Option<String>,
+    ///Units of `currency` per USD. If not set the rate is taken from the daily reference rates of the ECB.
+    #[serde(default)]
+    pub fx_rate: Option<f64>,
     pub replace: Vec<Replace>,
+    ///The path expressions used if `extractor` is `selectors`
+    #[serde(default)]
+    pub selectors: Selectors,
+    ///The patterns used to extract market cap, volume, rank and supply of a currency
+    #[serde(default)]
+    pub statistics: StatisticsPatterns,
+    ///When the daemon takes price snapshots. If not set a snapshot is taken every 5 minutes.
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    ///How prices and volumes are rounded per output. If not set they are written with all scraped digits.
+    #[serde(default)]
+    pub rounding: Option<Rounding>,
+    ///Rules checked against every fetched price, see `alert::AlertEngine`
+    #[serde(default)]
+    pub alerts: Option<Vec<AlertRule>>,
+    ///Where alerts are delivered to in addition to stderr
+    #[serde(default)]
+    pub notify: Option<Vec<NotifyConfig>>,
+}
+
+///The schedule of the snapshot daemon. Either `interval` or `cron` has to be set; `cron` is used if both are.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Schedule {
+    ///Seconds between two snapshots
+    #[serde(default)]
+    pub interval: Option<u64>,
+    ///Cron expression of the snapshot times, e.g. "*/5 * * * *". A leading seconds field is optional.
+    #[serde(default)]
+    pub cron: Option<String>,
+    ///Maximum seconds to wait before symbols which failed with a transient error are retried. Defaults to 300.
+    #[serde(default)]
+    pub max_backoff: Option<u64>,
+}
+///How a `money::Decimal` is rounded to fewer decimal places
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    ///Half to the nearest even digit ("banker's rounding"), e.g. 2.25 to 2.2 and 2.35 to 2.4
+    #[default]
+    HalfEven,
+    ///Half away from zero, e.g. 2.25 to 2.3 and -2.25 to -2.3
+    HalfUp,
+    ///Half towards zero, e.g. 2.25 to 2.2
+    HalfDown,
+    ///Towards zero, i.e. the dropped digits are cut off
+    Down,
+    ///Away from zero
+    Up,
+    ///Towards negative infinity
+    Floor,
+    ///Towards positive infinity
+    Ceiling,
+}
+
+///Rounding of the prices and volumes of one output, e.g. `{ places = 2, mode = "half_up" }`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RoundingRule {
+    ///Number of decimal places
+    pub places: u32,
+    ///Defaults to `half_even`
+    #[serde(default)]
+    pub mode: RoundingMode,
+}
+///The `RoundingRule` of every output. Outputs without a rule get all scraped digits.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Rounding {
+    ///The table format and the interactive menu
+    #[serde(default)]
+    pub table: Option<RoundingRule>,
+    ///The json and ndjson formats and the API server
+    #[serde(default)]
+    pub json: Option<RoundingRule>,
+    ///The csv and tsv formats
+    #[serde(default)]
+    pub csv: Option<RoundingRule>,
+    ///The workbook updated with `export`
+    #[serde(default)]
+    pub spreadsheet: Option<RoundingRule>,
+    ///The prices copied to the clipboard
+    #[serde(default)]
+    pub clipboard: Option<RoundingRule>,
+}
+///A rule raising an alert when the price of `symbol` meets `condition`, e.g.
+/// `{ symbol = "bitcoin", when = "above", price = 60000.0, hysteresis = 500.0 }`. Prices are always in USD, the
+/// rules are checked before the prices are converted into `currency`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AlertRule {
+    ///Name of the rule shown in the alerts. Defaults to a description of the condition.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub symbol: String,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+    ///How far (in the unit of the condition) the value has to move back before the rule can fire again.
+    /// Defaults to 0.
+    #[serde(default)]
+    pub hysteresis: Option<f64>,
+    ///Minimum seconds between two alerts of the rule. Defaults to 300.
+    #[serde(default)]
+    pub cooldown: Option<u64>,
+}
+
+///The condition of an `AlertRule`, selected by `when`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "when", rename_all = "snake_case")]
+pub enum AlertCondition {
+    ///The USD price is above `price`
+    Above { price: f64 },
+    ///The USD price is below `price`
+    Below { price: f64 },
+    ///The price moved by more than `percent` (up or down) within the last `window` seconds
+    Move { percent: f64, window: u64 },
+    ///The 24h change is above `percent`
+    ChangeAbove { percent: f64 },
+    ///The 24h change is below `percent`, e.g. -10.0
+    ChangeBelow { percent: f64 },
+    ///The USD price of a stablecoin left the band from `low` to `high` (default 0.99 to 1.01)
+    Depeg {
+        #[serde(default = "default_depeg_low")]
+        low: f64,
+        #[serde(default = "default_depeg_high")]
+        high: f64,
+    },
+}
+
+fn default_depeg_low() -> f64 {
+    0.99
 }
+fn default_depeg_high() -> f64 {
+    1.01
+}
+
+///A sink alerts are delivered to, e.g. `{ type = "webhook", url = "http://localhost:8080/alerts" }`.
+/// Alerts are delivered on a background thread of the sink. Failed deliveries are retried `retries` times,
+/// waiting `backoff` seconds before the first retry and twice as long before every further one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NotifyConfig {
+    #[serde(flatten)]
+    pub sink: NotifySink,
+    ///Number of retries of a failed delivery. Defaults to 3.
+    #[serde(default)]
+    pub retries: Option<u32>,
+    ///Seconds before the first retry. Defaults to 1.
+    #[serde(default)]
+    pub backoff: Option<u64>,
+}
+
+///The kind of a `NotifyConfig`, selected by `type`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifySink {
+    ///POSTs the alert to `url`. `template` is the JSON body in which `{{rule}}`, `{{symbol}}`, `{{message}}`,
+    /// `{{value}}` and `{{timestamp}}` are replaced by JSON values, e.g. `{"text": {{message}}}`.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        template: Option<String>,
+    },
+    ///Runs `command` with the shell. The alert is passed as JSON on stdin and in the environment variables
+    /// `ALERT_RULE`, `ALERT_SYMBOL`, `ALERT_MESSAGE`, `ALERT_VALUE` and `ALERT_TIMESTAMP`.
+    Command { command: String },
+    ///Appends a line per alert to the file at `path`
+    LogFile { path: String },
+}
+
 ///The Configuration instance containing configuratio details and file location
 pub struct ConfigObject {
     pub configuration: Config,
@@ -89,10 +408,26 @@ impl ConfigObject {
 
 #[cfg(test)]
 mod tests {
-    use super::ConfigObject;
+    use super::{
+        AlertCondition, Config, ConfigObject, RoundingMode, RoundingRule, StatisticsPatterns,
+    };
+    ///Returns the path of a copy of `config` for the test `case`. The config is stored again when it is dropped,
+    /// so the tests never rewrite the files of the repository.
+    fn temp_config(config: &str, case: &str) -> String {
+        let copy = std::env::temp_dir().join(format!("crypto_scrapper_config_{}.toml", case));
+        std::fs::copy(config, &copy).unwrap();
+        String::from(copy.to_str().unwrap())
+    }
+    #[test]
+    fn test_statistics() {
+        let text = std::fs::read_to_string("./config/test.toml").unwrap();
+        let config: Config = toml::from_str(&text).unwrap();
+        assert_eq!(config.statistics, StatisticsPatterns::default());
+        assert!(config.statistics.change_1h.is_empty());
+    }
     #[test]
     fn test_new() {
-        let config_file = ConfigObject::new(String::from("./config/test.toml"));
+        let config_file = ConfigObject::new(temp_config("./config/test.toml", "new")).unwrap();
         assert_eq!(
             config_file.configuration.about_regex,
             String::from("about_regex")
@@ -105,15 +440,80 @@ mod tests {
     }
     #[test]
     fn test_default() {
-        let config_file: ConfigObject = Default::default();
+        let mut config_file: ConfigObject = Default::default();
+        config_file.source = temp_config("./config/config.toml", "default");
         assert_eq!(
             config_file.configuration.about_regex,
-            String::from(r#"<(h2)\s{1}.{1,20}\s{1}(class=".*?")>About.{1,30}</h2>"#)
+            String::from(r#"<(div) (class="about___.{1,20}")>"#)
         );
         assert_eq!(
             config_file.configuration.what_is_regex,
             String::from(r#"<(h\d)\s{1}(.{1,20}="what-is-.*?")>.*?</h\d>"#)
         );
-        assert_eq!(config_file.configuration.symbols.len(), 3);
+        assert!(config_file
+            .configuration
+            .symbols
+            .contains(&String::from("bitcoin")));
+    }
+    #[test]
+    fn test_alerts() {
+        let mut text = std::fs::read_to_string("./config/test.toml").unwrap();
+        text.push_str(
+            r#"
+[[alerts]]
+symbol = "bitcoin"
+when = "above"
+price = 60000.0
+hysteresis = 500.0
+
+[[alerts]]
+symbol = "tether"
+when = "depeg"
+"#,
+        );
+        let config: Config = toml::from_str(&text).unwrap();
+        let alerts = config.alerts.as_ref().unwrap();
+        assert_eq!(
+            alerts[0].condition,
+            AlertCondition::Above { price: 60000.0 }
+        );
+        assert_eq!(alerts[0].hysteresis, Some(500.0));
+        assert_eq!(
+            alerts[1].condition,
+            AlertCondition::Depeg {
+                low: 0.99,
+                high: 1.01
+            }
+        );
+        let written: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(written.alerts, config.alerts);
+    }
+
+    #[test]
+    fn test_rounding() {
+        let mut text = std::fs::read_to_string("./config/test.toml").unwrap();
+        text.push_str(
+            r#"
+[rounding]
+table = { places = 2 }
+spreadsheet = { places = 4, mode = "half_up" }
+"#,
+        );
+        let config: Config = toml::from_str(&text).unwrap();
+        let rounding = config.rounding.as_ref().unwrap();
+        assert_eq!(
+            rounding.table,
+            Some(RoundingRule {
+                places: 2,
+                mode: RoundingMode::HalfEven
+            })
+        );
+        assert_eq!(rounding.json, None);
+        assert_eq!(
+            rounding.spreadsheet.as_ref().unwrap().mode,
+            RoundingMode::HalfUp
+        );
+        let written: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(written.rounding, config.rounding);
     }
 }
@@ -0,0 +1,298 @@
+use crate::config::Schedule;
+use crate::{CoinMarketCapScrapper, PriceBatch};
+use chrono::{DateTime, Local, Utc};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::Instant;
+
+///Seconds between two snapshots if the config has no schedule
+const DEFAULT_INTERVAL: u64 = 300;
+///Seconds before symbols which failed with a transient error are retried the first time
+const FIRST_BACKOFF: u64 = 5;
+///Default of the maximum seconds between retries
+const DEFAULT_MAX_BACKOFF: u64 = 300;
+
+///Errors which stop the daemon
+#[derive(Debug)]
+pub enum DaemonError {
+    ///The schedule of the config is not valid
+    InvalidSchedule { expression: String, details: String },
+    ///The handler for SIGINT/SIGTERM could not be installed
+    Signal { details: String },
+    ///The cron `expression` of the schedule does not fire anymore
+    ScheduleEnded { expression: String },
+}
+
+impl fmt::Display for DaemonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DaemonError::InvalidSchedule {
+                expression,
+                details,
+            } => write!(f, "Invalid schedule \"{}\": {}", expression, details),
+            DaemonError::Signal { details } => {
+                write!(f, "Could not listen for shutdown signals: {}", details)
+            }
+            DaemonError::ScheduleEnded { expression } => {
+                write!(f, "The schedule \"{}\" does not fire anymore", expression)
+            }
+        }
+    }
+}
+
+impl Error for DaemonError {}
+
+///The times at which the daemon takes snapshots
+pub enum Trigger {
+    ///A snapshot every `Duration`
+    Interval(Duration),
+    ///A snapshot at every time matching the cron expression
+    Cron(Box<cron::Schedule>),
+}
+
+impl Trigger {
+    ///Returns the `Trigger` of `schedule`, or a snapshot every 5 minutes if there is no schedule
+    /// # Errors
+    /// If the cron expression is not valid or never fires again, or the interval is 0 an error is returned
+    pub fn from_config(schedule: Option<&Schedule>) -> Result<Trigger, DaemonError> {
+        match schedule {
+            Some(Schedule {
+                cron: Some(expression),
+                ..
+            }) => Trigger::from_cron(expression),
+            Some(Schedule {
+                interval: Some(0), ..
+            }) => Err(DaemonError::InvalidSchedule {
+                expression: String::from("interval = 0"),
+                details: String::from("the interval has to be at least one second"),
+            }),
+            Some(Schedule {
+                interval: Some(seconds),
+                ..
+            }) => Ok(Trigger::Interval(Duration::from_secs(*seconds))),
+            _ => Ok(Trigger::Interval(Duration::from_secs(DEFAULT_INTERVAL))),
+        }
+    }
+    ///Returns the `Trigger` of the cron `expression`. Expressions with five fields (without seconds) are
+    /// accepted as well and fire at second 0.
+    /// # Errors
+    /// If the expression is not valid or never fires again (e.g. a year in the past) an error is returned
+    pub fn from_cron(expression: &str) -> Result<Trigger, DaemonError> {
+        let full = if expression.split_whitespace().count() == 5 {
+            format!("0 {}", expression)
+        } else {
+            String::from(expression)
+        };
+        match cron::Schedule::from_str(&full) {
+            Ok(s) if s.upcoming(Utc).next().is_none() => Err(DaemonError::ScheduleEnded {
+                expression: String::from(expression),
+            }),
+            Ok(s) => Ok(Trigger::Cron(Box::new(s))),
+            Err(e) => Err(DaemonError::InvalidSchedule {
+                expression: String::from(expression),
+                details: e.to_string(),
+            }),
+        }
+    }
+    ///Returns the time to wait from `now` until the next snapshot
+    /// # Errors
+    /// If the cron expression does not fire after `now` an error is returned
+    fn next_after(&self, now: DateTime<Utc>) -> Result<Duration, DaemonError> {
+        match self {
+            Trigger::Interval(d) => Ok(*d),
+            Trigger::Cron(schedule) => match schedule.after(&now).next() {
+                Some(next) => Ok((next - now).to_std().unwrap_or_default()),
+                None => Err(DaemonError::ScheduleEnded {
+                    expression: schedule.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+///Symbols which failed with a transient error and when they are retried
+struct Retry {
+    symbols: Vec<String>,
+    at: Instant,
+    backoff: Duration,
+}
+
+///Returns once SIGINT (Ctrl-C) or, on unix, SIGTERM is received
+pub(crate) async fn shutdown_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            r = tokio::signal::ctrl_c() => r,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+///Prints a one line summary of `batch` and its errors
+fn report(batch: &PriceBatch, retry: bool) {
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+    println!(
+        "{} {}{} prices, {} errors",
+        now,
+        if retry { "retry: " } else { "" },
+        batch.prices().count(),
+        batch.errors().count()
+    );
+    for (s, e) in batch.errors() {
+        eprintln!("{} Could not get the price of {}: {}", now, s, e);
+    }
+}
+
+impl CoinMarketCapScrapper {
+    ///Takes snapshots of the prices of all symbols in the config at the times of `trigger` until SIGINT or
+    /// SIGTERM is received. The prices are recorded in the store if one is configured.
+    /// Symbols which fail with a transient error are retried with exponential backoff. A fetch which is in
+    /// flight when the signal arrives is finished before the function returns.
+    /// # Arguments
+    /// * `trigger` - When snapshots are taken, see `Trigger::from_config`
+    /// # Errors
+    /// If the signal handlers can not be installed or the schedule does not fire anymore an error is returned
+    pub fn run_daemon(&self, trigger: &Trigger) -> Result<(), DaemonError> {
+        if self.store.is_none() {
+            eprintln!("No store is configured, the snapshots are not persisted.");
+        }
+        self.runtime
+            .block_on(self.snapshots(trigger, shutdown_signal()))
+    }
+
+    ///Takes snapshots at the times of `trigger` until `shutdown` completes
+    async fn snapshots(
+        &self,
+        trigger: &Trigger,
+        shutdown: impl Future<Output = io::Result<()>>,
+    ) -> Result<(), DaemonError> {
+        tokio::pin!(shutdown);
+        let max_backoff = Duration::from_secs(
+            self.cfg
+                .configuration
+                .schedule
+                .as_ref()
+                .and_then(|s| s.max_backoff)
+                .unwrap_or(DEFAULT_MAX_BACKOFF),
+        );
+        let mut next = Instant::now();
+        let mut retry: Option<Retry> = None;
+        loop {
+            let is_retry = matches!(&retry, Some(r) if r.at < next);
+            let deadline = match &retry {
+                Some(r) if is_retry => r.at,
+                _ => next,
+            };
+            //only wait for the signal between two fetches, so in-flight fetches are always finished
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => {}
+                r = &mut shutdown => {
+                    return r.map_err(|e| DaemonError::Signal {
+                        details: e.to_string(),
+                    })
+                }
+            }
+            let symbols = match &retry {
+                Some(r) if is_retry => r.symbols.clone(),
+                _ => self.cfg.get_symbols(),
+            };
+            let batch = self.fetch_prices(&symbols).await;
+            report(&batch, is_retry);
+            let failed: Vec<String> = batch
+                .errors()
+                .filter(|(_, e)| e.is_transient())
+                .map(|(s, _)| String::from(s))
+                .collect();
+            retry = if failed.is_empty() {
+                None
+            } else {
+                let backoff = match &retry {
+                    Some(r) if is_retry => (r.backoff * 2).min(max_backoff),
+                    _ => Duration::from_secs(FIRST_BACKOFF).min(max_backoff),
+                };
+                Some(Retry {
+                    symbols: failed,
+                    at: Instant::now() + backoff,
+                    backoff,
+                })
+            };
+            if !is_retry {
+                next = Instant::now() + trigger.next_after(Utc::now())?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DaemonError, Trigger};
+    use crate::config::Schedule;
+    use crate::html::FileFetcher;
+    use crate::store::Store;
+    use crate::CoinMarketCapScrapper;
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cron_trigger() {
+        let trigger = Trigger::from_cron("*/5 * * * *").unwrap();
+        let now = Utc.with_ymd_and_hms(2021, 2, 20, 10, 3, 30).unwrap();
+        assert_eq!(trigger.next_after(now).unwrap(), Duration::from_secs(90));
+        assert!(Trigger::from_cron("every five minutes").is_err());
+        match Trigger::from_cron("0 0 0 1 1 * 2020") {
+            Err(DaemonError::ScheduleEnded { expression }) => {
+                assert_eq!(expression, "0 0 0 1 1 * 2020")
+            }
+            _ => panic!("expected an ended schedule"),
+        }
+        let zero = Schedule {
+            interval: Some(0),
+            ..Default::default()
+        };
+        assert!(Trigger::from_config(Some(&zero)).is_err());
+    }
+
+    #[test]
+    fn test_snapshots_until_shutdown() {
+        let fetcher = FileFetcher::new("./").with_page(
+            "https://coinmarketcap.com/currencies/bitcoin/",
+            "bitcoin.html",
+        );
+        //the config is stored again when it is dropped
+        let config = std::env::temp_dir().join("crypto_scrapper_daemon.toml");
+        std::fs::copy("./tests/fixtures/config.toml", &config).unwrap();
+        let mut scrapper = CoinMarketCapScrapper::with_fetcher(
+            String::from(config.to_str().unwrap()),
+            Arc::new(fetcher),
+        )
+        .unwrap();
+        scrapper.store = Some(Store::open_in_memory().unwrap());
+        let trigger = Trigger::Interval(Duration::from_millis(20));
+        //shuts down after the second snapshot instead of a fixed time, so a busy machine does not fail the test
+        let shutdown = async {
+            while scrapper.latest_prices("bitcoin", 2).unwrap().len() < 2 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            Ok(())
+        };
+        let snapshots = async {
+            tokio::time::timeout(
+                Duration::from_secs(30),
+                scrapper.snapshots(&trigger, shutdown),
+            )
+            .await
+        };
+        scrapper.runtime.block_on(snapshots).unwrap().unwrap();
+        let snapshots = scrapper.latest_prices("bitcoin", 100).unwrap();
+        assert!(snapshots.len() >= 2);
+    }
+}
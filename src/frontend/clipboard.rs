@@ -0,0 +1,158 @@
+use std::io;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+///A place text can be copied to. `to_clip` tries the clipboards of the current platform in turn.
+pub trait Clipboard {
+    ///Returns a human readable name of the clipboard, e.g. "xclip"
+    fn name(&self) -> String;
+    ///Copies `text` to the clipboard
+    /// # Errors
+    /// If the clipboard is not available on this machine an error is returned
+    fn set_text(&self, text: &str) -> io::Result<()>;
+}
+
+///The native Windows clipboard
+#[cfg(windows)]
+pub struct WindowsClipboard;
+
+#[cfg(windows)]
+impl Clipboard for WindowsClipboard {
+    fn name(&self) -> String {
+        String::from("Windows clipboard")
+    }
+    fn set_text(&self, text: &str) -> io::Result<()> {
+        use clipboard_win::{formats, Setter};
+        let clipboard_error = |e: clipboard_win::ErrorCode| io::Error::other(e.to_string());
+        let _clip = clipboard_win::Clipboard::new_attempts(10).map_err(clipboard_error)?;
+        formats::Unicode
+            .write_clipboard(&text)
+            .map_err(clipboard_error)
+    }
+}
+
+///A clipboard which is written through a command line tool reading the text from stdin,
+/// like `wl-copy` on Wayland, `xclip` or `xsel` on X11 and `pbcopy` on macOS
+pub struct CommandClipboard {
+    program: String,
+    args: Vec<String>,
+}
+impl CommandClipboard {
+    ///Returns a new `CommandClipboard` running `program` with `args`
+    pub fn new(program: &str, args: &[&str]) -> CommandClipboard {
+        CommandClipboard {
+            program: String::from(program),
+            args: args.iter().map(|a| String::from(*a)).collect(),
+        }
+    }
+}
+
+impl Clipboard for CommandClipboard {
+    fn name(&self) -> String {
+        self.program.clone()
+    }
+    fn set_text(&self, text: &str) -> io::Result<()> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "{} exited with {}",
+                self.program, status
+            )))
+        }
+    }
+}
+
+///Writes the text to a file instead of a clipboard. Used on headless machines.
+pub struct FileClipboard {
+    path: String,
+}
+impl FileClipboard {
+    ///Returns a new `FileClipboard` writing to `path`
+    pub fn new(path: &str) -> FileClipboard {
+        FileClipboard {
+            path: String::from(path),
+        }
+    }
+}
+
+impl Clipboard for FileClipboard {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+    fn set_text(&self, text: &str) -> io::Result<()> {
+        std::fs::write(&self.path, text)
+    }
+}
+
+///Returns the clipboards of the current platform in the order in which they are tried
+pub fn system_clipboards() -> Vec<Box<dyn Clipboard>> {
+    let mut clipboards: Vec<Box<dyn Clipboard>> = Vec::new();
+    #[cfg(windows)]
+    clipboards.push(Box::new(WindowsClipboard));
+    #[cfg(target_os = "macos")]
+    clipboards.push(Box::new(CommandClipboard::new("pbcopy", &[])));
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        clipboards.push(Box::new(CommandClipboard::new("wl-copy", &[])));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        clipboards.push(Box::new(CommandClipboard::new(
+            "xclip",
+            &["-selection", "clipboard"],
+        )));
+        clipboards.push(Box::new(CommandClipboard::new(
+            "xsel",
+            &["--clipboard", "--input"],
+        )));
+    }
+    clipboards
+}
+
+///Copies `value` to the first clipboard of this machine which is available. If there is none (e.g. on a
+/// headless server) `value` is written to `fallback_file`, or to stdout if no file is given.
+/// Returns the name of the place the value was copied to.
+pub fn to_clip(value: String, fallback_file: Option<&str>) -> String {
+    let mut clipboards = system_clipboards();
+    if let Some(path) = fallback_file {
+        clipboards.push(Box::new(FileClipboard::new(path)));
+    }
+    for clipboard in clipboards {
+        if clipboard.set_text(&value).is_ok() {
+            return clipboard.name();
+        }
+    }
+    print!("{}", value);
+    String::from("stdout")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clipboard, CommandClipboard, FileClipboard};
+
+    #[test]
+    fn test_file_clipboard() {
+        let file = std::env::temp_dir().join("crypto_scrapper_clipboard.txt");
+        let clipboard = FileClipboard::new(file.to_str().unwrap());
+        clipboard.set_text("bitcoin\t49898.19\n").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "bitcoin\t49898.19\n"
+        );
+    }
+
+    #[test]
+    fn test_missing_command() {
+        let clipboard = CommandClipboard::new("crypto-scrapper-no-such-copy-tool", &[]);
+        assert!(clipboard.set_text("bitcoin").is_err());
+    }
+}
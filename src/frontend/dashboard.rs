@@ -0,0 +1,614 @@
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crypto_scrapper::config::RoundingRule;
+use crypto_scrapper::money::Decimal;
+use crypto_scrapper::{CoinMarketCapScrapper, MarketResult, PriceBatch};
+use std::io;
+use std::time::{Duration, Instant};
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::Span;
+use tui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap};
+use tui::{Frame, Terminal};
+
+///Number of prices per symbol shown in the sparkline
+const HISTORY_LENGTH: usize = 30;
+///Number of markets shown for the selected symbol
+const MARKET_LIMIT: i32 = 10;
+///Lines scrolled in the details with PageUp/PageDown
+const DETAILS_PAGE: u16 = 5;
+///Characters of the sparkline, from the lowest to the highest price
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+///The column the symbols are sorted by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortBy {
+    ///The order of the config
+    Config,
+    Symbol,
+    Price,
+    Change,
+}
+impl SortBy {
+    fn next(self) -> SortBy {
+        match self {
+            SortBy::Config => SortBy::Symbol,
+            SortBy::Symbol => SortBy::Price,
+            SortBy::Price => SortBy::Change,
+            SortBy::Change => SortBy::Config,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            SortBy::Config => "config",
+            SortBy::Symbol => "symbol",
+            SortBy::Price => "price",
+            SortBy::Change => "change",
+        }
+    }
+}
+
+///What the dashboard has to do after a key was pressed
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    None,
+    Quit,
+    Refresh,
+    ///Load the markets and details of the symbol
+    Load(String),
+    Add(String),
+    Remove(String),
+}
+
+///A symbol of the table with its last price and the prices before
+struct SymbolRow {
+    symbol: String,
+    ///Position of the symbol in the config
+    position: usize,
+    price: Option<(Decimal, f64)>,
+    ///The currency of `price` and `history`
+    currency: String,
+    error: Option<String>,
+    history: Vec<f64>,
+}
+
+///Returns `values` as a line of bar characters scaled between their minimum and maximum
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            if max > min {
+                let level = (v - min) / (max - min) * (SPARKS.len() - 1) as f64;
+                SPARKS[level.round() as usize]
+            } else {
+                SPARKS[SPARKS.len() / 2]
+            }
+        })
+        .collect()
+}
+
+///State of the full-screen dashboard. Keys are handled by `handle_key`, which returns what has to be scraped.
+pub struct Dashboard {
+    rows: Vec<SymbolRow>,
+    selected: usize,
+    sort: SortBy,
+    descending: bool,
+    ///The symbol being typed after pressing `a`
+    adding: Option<String>,
+    ///The symbol the markets and details were loaded for
+    loaded: Option<String>,
+    markets: String,
+    details: String,
+    details_scroll: u16,
+    status: String,
+    ///Rounding of the prices in the table
+    rounding: Option<RoundingRule>,
+}
+
+impl Dashboard {
+    ///Returns a new `Dashboard` showing `symbols` in the order of the config
+    pub fn new(symbols: &[String]) -> Dashboard {
+        let mut dashboard = Dashboard {
+            rows: Vec::new(),
+            selected: 0,
+            sort: SortBy::Config,
+            descending: false,
+            adding: None,
+            loaded: None,
+            markets: String::new(),
+            details: String::from("Press Enter to load the markets and details of a symbol."),
+            details_scroll: 0,
+            status: String::new(),
+            rounding: None,
+        };
+        for s in symbols {
+            dashboard.add_row(s);
+        }
+        dashboard
+    }
+
+    ///Returns the symbol of the selected row
+    pub fn selected_symbol(&self) -> Option<&str> {
+        self.rows.get(self.selected).map(|r| r.symbol.as_str())
+    }
+
+    ///Returns the symbols in the order they are shown
+    pub fn symbols(&self) -> Vec<&str> {
+        self.rows.iter().map(|r| r.symbol.as_str()).collect()
+    }
+
+    ///Adds a row for `symbol` at the end of the config
+    pub fn add_row(&mut self, symbol: &str) {
+        let position = self.rows.iter().map(|r| r.position + 1).max().unwrap_or(0);
+        self.rows.push(SymbolRow {
+            symbol: String::from(symbol),
+            position,
+            price: None,
+            error: None,
+            currency: String::new(),
+            history: Vec::new(),
+        });
+        self.sort();
+    }
+
+    ///Removes the row of `symbol`
+    pub fn remove_row(&mut self, symbol: &str) {
+        if let Some(i) = self.rows.iter().position(|r| r.symbol == symbol) {
+            let removed = self.rows.remove(i);
+            for row in self.rows.iter_mut() {
+                if row.position > removed.position {
+                    row.position -= 1;
+                }
+            }
+            if self.selected >= self.rows.len() && self.selected > 0 {
+                self.selected -= 1;
+            }
+        }
+    }
+
+    ///Sets the rounding of the prices shown in the table
+    pub fn set_rounding(&mut self, rule: Option<RoundingRule>) {
+        self.rounding = rule;
+    }
+
+    ///Sets the earlier prices of `symbol` in `currency` shown in its sparkline, oldest first
+    pub fn set_history(&mut self, symbol: &str, prices: Vec<f64>, currency: &str) {
+        if let Some(row) = self.rows.iter_mut().find(|r| r.symbol == symbol) {
+            row.history = prices;
+            row.currency = String::from(currency);
+            let len = row.history.len();
+            row.history.drain(..len.saturating_sub(HISTORY_LENGTH));
+        }
+    }
+
+    ///Updates the rows with the prices and errors of `batch`
+    pub fn apply_prices(&mut self, batch: &PriceBatch) {
+        for (symbol, result) in batch.iter() {
+            let row = match self.rows.iter_mut().find(|r| &r.symbol == symbol) {
+                Some(r) => r,
+                None => continue,
+            };
+            match result {
+                Ok(p) => {
+                    if row.currency != p.currency() {
+                        //the sparkline never mixes prices of different currencies
+                        row.history.clear();
+                        row.currency = String::from(p.currency());
+                    }
+                    row.price = Some((p.price(), p.change()));
+                    row.error = None;
+                    row.history.push(p.price().to_f64());
+                    let len = row.history.len();
+                    row.history.drain(..len.saturating_sub(HISTORY_LENGTH));
+                }
+                Err(e) => row.error = Some(e.to_string()),
+            }
+        }
+        self.sort();
+    }
+
+    ///Shows the markets and details loaded for `symbol`
+    pub fn set_loaded(&mut self, symbol: &str, markets: String, details: String) {
+        self.loaded = Some(String::from(symbol));
+        self.markets = markets;
+        self.details = details;
+        self.details_scroll = 0;
+    }
+
+    ///Sets the message shown in the status line
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    ///Sorts the rows by `sort` keeping the selected symbol selected
+    fn sort(&mut self) {
+        let selected = self.selected_symbol().map(String::from);
+        let key = |r: &SymbolRow| match r.price {
+            Some((price, change)) => (price.to_f64(), change),
+            None => (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        };
+        match self.sort {
+            SortBy::Config => self.rows.sort_by_key(|r| r.position),
+            SortBy::Symbol => self.rows.sort_by(|a, b| a.symbol.cmp(&b.symbol)),
+            SortBy::Price => self.rows.sort_by(|a, b| key(a).0.total_cmp(&key(b).0)),
+            SortBy::Change => self.rows.sort_by(|a, b| key(a).1.total_cmp(&key(b).1)),
+        }
+        if self.descending {
+            self.rows.reverse();
+        }
+        if let Some(s) = selected {
+            self.selected = self.rows.iter().position(|r| r.symbol == s).unwrap_or(0);
+        }
+    }
+
+    ///Updates the state with the pressed `key` and returns what has to be done
+    pub fn handle_key(&mut self, key: KeyCode) -> Action {
+        if let Some(symbol) = &mut self.adding {
+            match key {
+                KeyCode::Char(c) => symbol.push(c),
+                KeyCode::Backspace => {
+                    symbol.pop();
+                }
+                KeyCode::Enter => {
+                    let symbol = String::from(symbol.trim());
+                    self.adding = None;
+                    if !symbol.is_empty() && !self.rows.iter().any(|r| r.symbol == symbol) {
+                        return Action::Add(symbol);
+                    }
+                }
+                KeyCode::Esc => self.adding = None,
+                _ => {}
+            }
+            return Action::None;
+        }
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+            KeyCode::Char('r') => Action::Refresh,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                Action::None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.selected + 1 < self.rows.len() {
+                    self.selected += 1;
+                }
+                Action::None
+            }
+            KeyCode::Enter => match self.selected_symbol() {
+                Some(s) => Action::Load(String::from(s)),
+                None => Action::None,
+            },
+            KeyCode::Char('a') => {
+                self.adding = Some(String::new());
+                Action::None
+            }
+            KeyCode::Char('d') | KeyCode::Delete => match self.selected_symbol() {
+                Some(s) => Action::Remove(String::from(s)),
+                None => Action::None,
+            },
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.sort();
+                Action::None
+            }
+            KeyCode::Char('o') => {
+                self.descending = !self.descending;
+                self.sort();
+                Action::None
+            }
+            KeyCode::PageDown => {
+                self.details_scroll = self.details_scroll.saturating_add(DETAILS_PAGE);
+                Action::None
+            }
+            KeyCode::PageUp => {
+                self.details_scroll = self.details_scroll.saturating_sub(DETAILS_PAGE);
+                Action::None
+            }
+            _ => Action::None,
+        }
+    }
+
+    ///Draws the dashboard on the whole frame
+    fn draw<B: Backend>(&self, f: &mut Frame<B>) {
+        let screen = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(5), Constraint::Length(1)].as_ref())
+            .split(f.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(screen[0]);
+        let panes = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(MARKET_LIMIT as u16 + 3),
+                    Constraint::Min(3),
+                ]
+                .as_ref(),
+            )
+            .split(columns[1]);
+
+        let rows = self.rows.iter().map(|r| {
+            let (price, change) = match (r.price, &r.error) {
+                (_, Some(_)) => (
+                    Cell::from(Span::styled("error", Style::default().fg(Color::Yellow))),
+                    Cell::from(""),
+                ),
+                (Some((price, change)), None) => {
+                    let color = if change >= 0.0 {
+                        Color::Green
+                    } else {
+                        Color::Red
+                    };
+                    let price = match &self.rounding {
+                        Some(rule) => price.round(rule.places, rule.mode),
+                        None => price,
+                    };
+                    (
+                        Cell::from(format!("{} {}", price, r.currency)),
+                        Cell::from(Span::styled(
+                            format!("{:+.2}%", change),
+                            Style::default().fg(color),
+                        )),
+                    )
+                }
+                (None, None) => (Cell::from("..."), Cell::from("")),
+            };
+            Row::new(vec![
+                Cell::from(r.symbol.clone()),
+                price,
+                change,
+                Cell::from(sparkline(&r.history)),
+            ])
+        });
+        let title = format!(
+            "Prices (sorted by {}{})",
+            self.sort.name(),
+            if self.descending { ", descending" } else { "" }
+        );
+        let widths = [
+            Constraint::Percentage(30),
+            Constraint::Percentage(20),
+            Constraint::Percentage(12),
+            Constraint::Percentage(38),
+        ];
+        let table = Table::new(rows)
+            .header(
+                Row::new(vec!["Symbol", "Price", "24h", "Trend"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .widths(&widths)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default();
+        if !self.rows.is_empty() {
+            state.select(Some(self.selected));
+        }
+        f.render_stateful_widget(table, columns[0], &mut state);
+
+        let loaded = self.loaded.as_deref().unwrap_or("");
+        let markets = Paragraph::new(self.markets.as_str()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Markets {}", loaded)),
+        );
+        f.render_widget(markets, panes[0]);
+        let details = Paragraph::new(self.details.as_str())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Details {}", loaded)),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((self.details_scroll, 0));
+        f.render_widget(details, panes[1]);
+
+        let status = match &self.adding {
+            Some(symbol) => format!("Add symbol: {}_  (Enter to add, Esc to cancel)", symbol),
+            None => format!(
+                "{}  q quit  r refresh  ↑↓ select  Enter markets/details  a add  d remove  s sort  o order  PgUp/PgDn scroll",
+                self.status
+            ),
+        };
+        f.render_widget(Paragraph::new(status), screen[1]);
+    }
+}
+
+///Converts an error of crossterm into an `io::Error`
+fn terminal_error(e: crossterm::ErrorKind) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+///Returns the markets of `markets` rounded by `rule` as a table, or the error
+fn market_text(markets: Result<Vec<MarketResult>, String>, rule: Option<&RoundingRule>) -> String {
+    match markets {
+        Ok(m) => m.iter().fold(MarketResult::get_header(), |text, m| {
+            format!("{}\n{}", text, m.rounded(rule))
+        }),
+        Err(e) => e,
+    }
+}
+
+///Runs the event loop of the dashboard until `q` is pressed. The prices are scraped again every `refresh`.
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    scrapper: &mut CoinMarketCapScrapper,
+    refresh: Duration,
+) -> io::Result<()> {
+    let mut dashboard = Dashboard::new(&scrapper.cfg.get_symbols());
+    let rule = super::table_rule(scrapper);
+    dashboard.set_rounding(rule.clone());
+    let currency = scrapper.currency();
+    for symbol in scrapper.cfg.get_symbols() {
+        //earlier snapshots of the store, if one is configured
+        if let Ok(snapshots) = scrapper.latest_prices(&symbol, HISTORY_LENGTH as u32) {
+            let prices = snapshots
+                .iter()
+                .rev()
+                .filter(|s| s.price.currency() == currency)
+                .map(|s| s.price.price().to_f64())
+                .collect();
+            dashboard.set_history(&symbol, prices, &currency);
+        }
+    }
+    let mut last_refresh: Option<Instant> = None;
+    loop {
+        if last_refresh.is_none_or(|t| t.elapsed() >= refresh) {
+            dashboard.set_status(String::from("Refreshing..."));
+            terminal.draw(|f| dashboard.draw(f))?;
+            let batch = scrapper.get_all_prices();
+            dashboard.apply_prices(&batch);
+            dashboard.set_status(format!(
+                "Updated {} of {} at {}.",
+                batch.prices().count(),
+                batch.len(),
+                chrono::Local::now().format("%H:%M:%S")
+            ));
+            last_refresh = Some(Instant::now());
+        }
+        terminal.draw(|f| dashboard.draw(f))?;
+        let elapsed = last_refresh.map(|t| t.elapsed()).unwrap_or_default();
+        if !event::poll(refresh.saturating_sub(elapsed)).map_err(terminal_error)? {
+            continue;
+        }
+        let key = match event::read().map_err(terminal_error)? {
+            Event::Key(key) => key.code,
+            _ => continue,
+        };
+        match dashboard.handle_key(key) {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Refresh => last_refresh = None,
+            Action::Load(symbol) => {
+                dashboard.set_status(format!("Loading {}...", symbol));
+                terminal.draw(|f| dashboard.draw(f))?;
+                let markets = scrapper
+                    .get_market_data(&symbol, MARKET_LIMIT)
+                    .map_err(|e| e.to_string());
+                let details = match scrapper.get_details(&symbol) {
+                    Ok(d) => d,
+                    Err(e) => e.to_string(),
+                };
+                dashboard.set_loaded(&symbol, market_text(markets, rule.as_ref()), details);
+                dashboard.set_status(String::new());
+            }
+            Action::Add(symbol) => {
+                scrapper.cfg.add_symbol(symbol.clone());
+                dashboard.add_row(&symbol);
+                last_refresh = None;
+            }
+            Action::Remove(symbol) => {
+                if let Some(i) = scrapper.cfg.get_symbols().iter().position(|s| s == &symbol) {
+                    scrapper.cfg.delete_symbol(i);
+                }
+                dashboard.remove_row(&symbol);
+                dashboard.set_status(format!("{} removed.", symbol));
+            }
+        }
+    }
+}
+
+///Shows the full-screen dashboard until `q` is pressed. The prices of the symbols in the config are scraped
+/// again every `refresh`. Added and removed symbols are stored in the config.
+/// # Errors
+/// If the terminal can not be used an error is returned
+pub fn run_dashboard(scrapper: &mut CoinMarketCapScrapper, refresh: Duration) -> io::Result<()> {
+    enable_raw_mode().map_err(terminal_error)?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen).map_err(terminal_error)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let result = event_loop(&mut terminal, scrapper, refresh);
+    //restore the terminal even if the loop failed
+    disable_raw_mode().map_err(terminal_error)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen).map_err(terminal_error)?;
+    terminal.show_cursor()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sparkline, Action, Dashboard};
+    use crossterm::event::KeyCode;
+    use crypto_scrapper::config::{RoundingMode, RoundingRule};
+    use tui::backend::TestBackend;
+    use tui::Terminal;
+
+    fn symbols() -> Vec<String> {
+        vec![
+            String::from("iota"),
+            String::from("bitcoin"),
+            String::from("cardano"),
+        ]
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[1.0, 2.0, 3.0, 8.0]), "▁▂▃█");
+        assert_eq!(sparkline(&[5.0, 5.0]), "▅▅");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut dashboard = Dashboard::new(&symbols());
+        assert_eq!(dashboard.handle_key(KeyCode::Down), Action::None);
+        assert_eq!(
+            dashboard.handle_key(KeyCode::Enter),
+            Action::Load(String::from("bitcoin"))
+        );
+        //sorting by symbol keeps bitcoin selected
+        dashboard.handle_key(KeyCode::Char('s'));
+        assert_eq!(dashboard.symbols(), vec!["bitcoin", "cardano", "iota"]);
+        assert_eq!(dashboard.selected_symbol(), Some("bitcoin"));
+        dashboard.handle_key(KeyCode::Char('o'));
+        assert_eq!(dashboard.symbols(), vec!["iota", "cardano", "bitcoin"]);
+
+        dashboard.handle_key(KeyCode::Char('a'));
+        for c in "dash".chars() {
+            assert_eq!(dashboard.handle_key(KeyCode::Char(c)), Action::None);
+        }
+        assert_eq!(
+            dashboard.handle_key(KeyCode::Enter),
+            Action::Add(String::from("dash"))
+        );
+        dashboard.add_row("dash");
+        assert_eq!(
+            dashboard.handle_key(KeyCode::Char('d')),
+            Action::Remove(String::from("bitcoin"))
+        );
+        dashboard.remove_row("bitcoin");
+        assert_eq!(dashboard.symbols(), vec!["iota", "dash", "cardano"]);
+        assert_eq!(dashboard.handle_key(KeyCode::Char('q')), Action::Quit);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut dashboard = Dashboard::new(&symbols());
+        dashboard.set_history("bitcoin", vec![48000.0, 49000.0, 50000.0], "USD");
+        dashboard.rows[1].price = Some(("49898.18643924657".parse().unwrap(), -1.48));
+        dashboard.set_rounding(Some(RoundingRule {
+            places: 2,
+            mode: RoundingMode::HalfEven,
+        }));
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|f| dashboard.draw(f)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|c| c.symbol.as_str())
+            .collect();
+        assert!(screen.contains("Prices (sorted by config)"));
+        assert!(screen.contains("bitcoin"));
+        assert!(screen.contains("▁▅█"));
+        assert!(screen.contains("49898.19"));
+    }
+}
@@ -1,12 +1,14 @@
-use clipboard_win::{formats, Clipboard, Setter};
+pub mod clipboard;
+pub mod dashboard;
+
+pub use clipboard::to_clip;
+use crypto_scrapper::config::RoundingRule;
+use crypto_scrapper::spreadsheet::update_workbook;
 use crypto_scrapper::CoinMarketCapScrapper;
 use crypto_scrapper::MarketResult;
+pub use dashboard::run_dashboard;
+use std::time::SystemTime;
 
-///Funtion to copy the String stored in `value`to the System Clipboard.
-pub fn to_clip(value: String) {
-    let _clip = Clipboard::new_attempts(10).unwrap();
-    formats::Unicode.write_clipboard(&value).unwrap();
-}
 ///Reads the input from the `stdin` and returns the trimmed version as a String
 fn read_std_input() -> String {
     let mut line = String::new();
@@ -24,6 +26,14 @@ fn get_indexed_symbols(symbols: &Vec<String>) -> String {
     })
 }
 
+///Returns the rounding of prices and volumes printed as a table
+fn table_rule(scrapper: &CoinMarketCapScrapper) -> Option<RoundingRule> {
+    match &scrapper.cfg.configuration.rounding {
+        Some(r) => r.table.clone(),
+        None => None,
+    }
+}
+
 ///The main menu loop of the Commandline Interface
 pub fn cli_menu(mut scrapper: CoinMarketCapScrapper) {
     let mut level = 0;
@@ -36,23 +46,50 @@ pub fn cli_menu(mut scrapper: CoinMarketCapScrapper) {
             println!("{}", menu_level_index);
             input = read_std_input();
             if input == "1" {
-                let price_result = scrapper.get_all_prices();
-                if let Err(e) = price_result {
-                    println!("{}", e);
-                    continue;
-                };
-                let prices = price_result.unwrap();
-                let result = prices.iter().fold(String::new(), |clip, x| {
-                    format!("{}{}\n", clip, x.to_string())
+                let prices = scrapper.get_all_prices();
+                let rounding = scrapper
+                    .cfg
+                    .configuration
+                    .rounding
+                    .clone()
+                    .unwrap_or_default();
+                //failed symbols keep their row and are flagged with #N/A for Excel
+                let result = prices.iter().fold(String::new(), |clip, (s, x)| match x {
+                    Ok(x) => {
+                        let x = x.rounded(rounding.clipboard.as_ref());
+                        format!("{}{}\t{}\n", clip, x.symbol(), x.price())
+                    }
+                    Err(_) => format!("{}{}\t#N/A\n", clip, s),
                 });
-                to_clip(result);
+                let target = to_clip(result, scrapper.cfg.configuration.clipboard_file.as_deref());
                 println!(
                     "{}",
                     prices
-                        .iter()
+                        .prices()
+                        .map(|x| x.rounded(rounding.table.as_ref()))
                         .fold(String::new(), |clip, x| format!("{}{}\n", clip, x))
                 );
-                println!("Prices also copied to Clipboard")
+                for (s, e) in prices.errors() {
+                    println!("Could not get the price of {}: {}", s, e);
+                }
+                println!("Prices also copied to {}", target);
+                if let Some(workbook) = &scrapper.cfg.configuration.workbook {
+                    let sheet = scrapper
+                        .cfg
+                        .configuration
+                        .workbook_sheet
+                        .as_deref()
+                        .unwrap_or("Prices");
+                    let rounded: Vec<_> = prices
+                        .prices()
+                        .map(|x| x.rounded(rounding.spreadsheet.as_ref()))
+                        .collect();
+                    let results: Vec<_> = rounded.iter().collect();
+                    match update_workbook(workbook, sheet, &results, SystemTime::now()) {
+                        Ok(_) => println!("Prices written to {} ({})", workbook, sheet),
+                        Err(e) => println!("Could not update the workbook: {}", e),
+                    }
+                }
             } else if input == "2" {
                 level = 2;
             //whatis
@@ -102,6 +139,7 @@ pub fn cli_menu(mut scrapper: CoinMarketCapScrapper) {
                 level = 0;
             }
         } else if level == 3 {
+            let rule = table_rule(&scrapper);
             let level_index = "1. By symbol\n2. By index\n3. Back to Main Menu";
             let menu_level_index = format!("Level {}\n{}", level, level_index);
             println!("{}", menu_level_index);
@@ -111,7 +149,8 @@ pub fn cli_menu(mut scrapper: CoinMarketCapScrapper) {
                 input = read_std_input();
                 if let Ok(r) = scrapper.get_market_data(&input, 3) {
                     println!("{}", MarketResult::get_header());
-                    r.iter().for_each(|r| println!("{}", r));
+                    r.iter()
+                        .for_each(|r| println!("{}", r.rounded(rule.as_ref())));
                 } else {
                     println!("Symbol not recognized. Try again.");
                 }
@@ -127,7 +166,8 @@ pub fn cli_menu(mut scrapper: CoinMarketCapScrapper) {
                 if j <= i {
                     if let Ok(r) = scrapper.get_market_data(symbols.get(j - 1).unwrap(), 3) {
                         println!("{}", MarketResult::get_header());
-                        r.iter().for_each(|r| println!("{}", r));
+                        r.iter()
+                            .for_each(|r| println!("{}", r.rounded(rule.as_ref())));
                     } else {
                         println!("Symbol not recognized. Try again.");
                     }
@@ -0,0 +1,89 @@
+use crate::html::ParseError;
+use crate::money::{Decimal, MAX_SCALE};
+use regex::Regex;
+use std::collections::HashMap;
+
+///The currency CoinMarketCap quotes all prices and volumes in
+pub const USD: &str = "USD";
+///The daily euro foreign exchange reference rates of the European Central Bank
+pub const ECB_RATES_URL: &str = "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml";
+///Seconds after which cached reference rates are loaded again. The ECB publishes them once per working day.
+pub const RATES_REFRESH_AFTER: u64 = 6 * 60 * 60;
+
+///Parses the reference rates published by the ECB at `ECB_RATES_URL`
+/// # Arguments
+/// * `xml` - The content of the rates file
+/// # Returns
+/// The units of every currency per EUR by ISO 4217 code, including EUR itself
+/// # Errors
+/// If the file does not contain any rate an error is returned
+pub fn parse_ecb_rates(xml: &str) -> Result<HashMap<String, Decimal>, ParseError> {
+    let pattern = r#"currency=['"]([A-Z]{3})['"]\s+rate=['"]([0-9.]+)['"]"#;
+    let regex = Regex::new(pattern).unwrap();
+    let mut rates = HashMap::new();
+    for cap in regex.captures_iter(xml) {
+        match cap[2].parse::<Decimal>() {
+            Ok(rate) => rates.insert(String::from(&cap[1]), rate),
+            Err(_) => {
+                return Err(ParseError::NumberParse {
+                    text: String::from(&cap[2]),
+                })
+            }
+        };
+    }
+    if rates.is_empty() {
+        return Err(ParseError::ElementNotFound {
+            pattern: String::from(pattern),
+        });
+    }
+    rates.insert(String::from("EUR"), Decimal::ONE);
+    Ok(rates)
+}
+
+///Returns the units of `currency` per USD with `MAX_SCALE` decimal places
+/// # Arguments
+/// * `rates` - The units of every currency per EUR as returned by `parse_ecb_rates`
+/// * `currency` - The ISO 4217 code of the currency, e.g. "CHF"
+/// # Errors
+/// If `rates` holds no rate for `currency` or USD an error is returned
+pub fn usd_rate(rates: &HashMap<String, Decimal>, currency: &str) -> Result<Decimal, ParseError> {
+    let rate = |code: &str| match rates.get(code) {
+        Some(r) if *r > Decimal::ZERO => Ok(*r),
+        _ => Err(ParseError::UnknownCurrency {
+            currency: String::from(code),
+        }),
+    };
+    let (rate, usd) = (rate(currency)?, rate(USD)?);
+    match rate.checked_div(usd, MAX_SCALE) {
+        Some(r) => Ok(r),
+        None => Err(ParseError::NumberParse {
+            text: format!("{} / {}", rate, usd),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ecb_rates, usd_rate};
+    use crate::html::ParseError;
+    use crate::money::Decimal;
+
+    fn d(text: &str) -> Decimal {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rates() {
+        let xml = std::fs::read_to_string("./tests/fixtures/eurofxref-daily.xml").unwrap();
+        let rates = parse_ecb_rates(&xml).unwrap();
+        assert_eq!(rates["CHF"], d("1.0937"));
+        assert_eq!(usd_rate(&rates, "USD").unwrap(), d("1"));
+        assert_eq!(usd_rate(&rates, "EUR").unwrap(), d("0.824198466990851397"));
+        assert_eq!(usd_rate(&rates, "CHF").unwrap(), d("0.901425863347894173"));
+        match usd_rate(&rates, "XYZ") {
+            Err(ParseError::UnknownCurrency { currency }) => assert_eq!(currency, "XYZ"),
+            r => panic!("expected an unknown currency, got {:?}", r),
+        }
+        assert!(parse_ecb_rates("<html></html>").is_err());
+    }
+}
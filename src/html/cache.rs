@@ -0,0 +1,359 @@
+use super::fetcher::FileFetcher;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+///A simple cache to store `ChacheEntries` and a `Duration` after which an existing item is out-dated.
+/// If the cache has a `directory` every entry is also written to disk, so that it survives a restart and can be
+/// shared by several processes pointing to the same directory.
+/// The number of entries and the size of the stored html sources can be limited with `set_limits`. If a limit is
+/// exceeded the least recently used entries are evicted from memory.
+pub struct Cache {
+    entries: HashMap<String, CacheEntry>,
+    refresh_after: Duration,
+    directory: Option<PathBuf>,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    bytes: usize,
+    uses: u64,
+    statistics: CacheStatistics,
+}
+
+///Describes how long a page may be served from the `Cache` before it has to be loaded again
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CachePolicy {
+    ///The page is always loaded again and never stored in the cache
+    Never,
+    ///The page is loaded again if the cached entry is older than the `Duration`
+    RefreshAfter(Duration),
+    ///The page is loaded once and then served from the cache
+    Forever,
+}
+impl CachePolicy {
+    ///Returns the policy for a refresh time in seconds as stored in the configuration file.
+    /// `Some(0)` means never cache, `None` means `default`.
+    pub fn from_config(refresh_after: Option<u64>, default: CachePolicy) -> CachePolicy {
+        match refresh_after {
+            Some(0) => CachePolicy::Never,
+            Some(seconds) => CachePolicy::RefreshAfter(Duration::new(seconds, 0)),
+            None => default,
+        }
+    }
+    ///Returns true if `entry` may still be served from the cache
+    fn allows(&self, entry: &CacheEntry) -> bool {
+        match self {
+            CachePolicy::Never => false,
+            CachePolicy::RefreshAfter(refresh_after) => !entry.is_outdated(*refresh_after),
+            CachePolicy::Forever => true,
+        }
+    }
+}
+
+///Counters describing how a `Cache` has been used
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStatistics {
+    ///Number of lookups answered from the cache
+    pub hits: u64,
+    ///Number of lookups for which the cache held no entry
+    pub misses: u64,
+    ///Number of lookups for which the cache only held an out-dated entry
+    pub stale: u64,
+    ///Number of entries evicted because a limit was exceeded
+    pub evictions: u64,
+    ///Number of entries currently held in memory
+    pub entries: usize,
+    ///Size of the html sources currently held in memory in bytes
+    pub bytes: usize,
+}
+impl Cache {
+    ///Returns a new in-memory `Cache` whose entries are out-dated after `refresh_after` seconds
+    pub fn new(refresh_after: u64) -> Cache {
+        Cache {
+            entries: HashMap::new(),
+            refresh_after: Duration::new(refresh_after, 0),
+            directory: None,
+            max_entries: None,
+            max_bytes: None,
+            bytes: 0,
+            uses: 0,
+            statistics: CacheStatistics::default(),
+        }
+    }
+
+    ///Limits the number of entries and the total size of the html sources held in memory. `None` means no limit.
+    /// Entries exceeding the limits are evicted immediately, least recently used first.
+    /// # Arguments
+    /// * `max_entries` - The maximum number of entries
+    /// * `max_bytes` - The maximum size of all html sources in bytes
+    pub fn set_limits(&mut self, max_entries: Option<usize>, max_bytes: Option<usize>) {
+        self.max_entries = max_entries;
+        self.max_bytes = max_bytes;
+        self.evict();
+    }
+
+    ///Returns the hit/miss/stale/eviction counters and the current size of the cache
+    pub fn statistics(&self) -> CacheStatistics {
+        CacheStatistics {
+            entries: self.entries.len(),
+            bytes: self.bytes,
+            ..self.statistics
+        }
+    }
+
+    ///Returns a new `Cache` whose entries are out-dated after `refresh_after` seconds and which stores its entries
+    /// in `directory`. Entries already stored in `directory` (e.g. by an earlier run or another process) are used
+    /// as long as they are not out-dated.
+    /// # Arguments
+    /// * `refresh_after` - Number of seconds after which an entry is out-dated
+    /// * `directory` - The directory in which the entries are stored. It is created if it does not exist.
+    pub fn with_directory(refresh_after: u64, directory: &str) -> Cache {
+        Cache {
+            directory: Some(PathBuf::from(directory)),
+            ..Cache::new(refresh_after)
+        }
+    }
+
+    ///Returns the entry stored for `key`. If `reload` is true an out-dated entry is not returned.
+    pub fn get(&mut self, key: &str, reload: bool) -> Option<&CacheEntry> {
+        let policy = if reload {
+            CachePolicy::RefreshAfter(self.refresh_after)
+        } else {
+            CachePolicy::Forever
+        };
+        self.get_with_policy(key, policy)
+    }
+
+    ///Returns the entry stored for `key` if `policy` allows to serve it from the cache
+    pub fn get_with_policy(&mut self, key: &str, policy: CachePolicy) -> Option<&CacheEntry> {
+        if policy == CachePolicy::Never {
+            self.statistics.misses += 1;
+            return None;
+        }
+        let (in_memory, outdated) = match self.entries.get(key) {
+            Some(entry) => {
+                let outdated = !policy.allows(entry);
+                (!outdated, outdated)
+            }
+            None => (false, false),
+        };
+        if !in_memory {
+            //another process might have stored a newer entry in the meantime
+            match self.read_entry(key) {
+                Some(entry) if policy.allows(&entry) => {
+                    self.store(key, entry);
+                }
+                Some(_) => {
+                    self.statistics.stale += 1;
+                    return None;
+                }
+                None if outdated => {
+                    self.statistics.stale += 1;
+                    return None;
+                }
+                None => {
+                    self.statistics.misses += 1;
+                    return None;
+                }
+            }
+        }
+        self.statistics.hits += 1;
+        self.uses += 1;
+        let uses = self.uses;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = uses;
+        Some(entry)
+    }
+
+    pub fn insert(&mut self, key: &str, value: &String) {
+        let entry = CacheEntry {
+            html: String::from(value),
+            timestamp: SystemTime::now(),
+            last_used: 0,
+        };
+        if let Err(e) = self.write_entry(key, &entry) {
+            eprintln!("Could not store {} in the cache directory: {}", key, e);
+        }
+        self.store(key, entry);
+    }
+
+    ///Stores `entry` in memory as the most recently used entry and evicts entries exceeding the limits
+    fn store(&mut self, key: &str, mut entry: CacheEntry) {
+        self.uses += 1;
+        entry.last_used = self.uses;
+        self.bytes += entry.html.len();
+        if let Some(old) = self.entries.insert(String::from(key), entry) {
+            self.bytes -= old.html.len();
+        }
+        self.evict();
+    }
+
+    ///Removes the least recently used entries until the cache is within its limits
+    fn evict(&mut self) {
+        loop {
+            let too_many = match self.max_entries {
+                Some(max) => self.entries.len() > max,
+                None => false,
+            };
+            let too_big = match self.max_bytes {
+                Some(max) => self.bytes > max,
+                None => false,
+            };
+            if !too_many && !too_big {
+                return;
+            }
+            let oldest = match self.entries.iter().min_by_key(|(_, e)| e.last_used) {
+                Some((key, _)) => key.clone(),
+                None => return,
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.bytes -= entry.html.len();
+                self.statistics.evictions += 1;
+            }
+        }
+    }
+
+    ///Returns the location of the file storing the entry `key`, if the cache has a directory
+    fn entry_file(&self, key: &str) -> Option<PathBuf> {
+        self.directory
+            .as_ref()
+            .map(|d| d.join(FileFetcher::file_name(key)))
+    }
+
+    ///Reads the entry `key` from the cache directory. The first line of the file holds the time the entry was
+    /// fetched in seconds since the unix epoch, the rest is the html source.
+    fn read_entry(&self, key: &str) -> Option<CacheEntry> {
+        let file = self.entry_file(key)?;
+        let content = std::fs::read_to_string(file).ok()?;
+        let mut parts = content.splitn(2, '\n');
+        let seconds = parts.next()?.parse::<u64>().ok()?;
+        let html = parts.next()?;
+        Some(CacheEntry {
+            html: String::from(html),
+            timestamp: UNIX_EPOCH + Duration::from_secs(seconds),
+            last_used: 0,
+        })
+    }
+
+    ///Writes `entry` to the cache directory. The entry is written to a temporary file first which is then renamed,
+    /// so that other processes never read a partially written entry.
+    fn write_entry(&self, key: &str, entry: &CacheEntry) -> std::io::Result<()> {
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        let file = match self.entry_file(key) {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+        let directory = self.directory.as_ref().unwrap();
+        std::fs::create_dir_all(directory)?;
+        let temp = directory.join(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            WRITES.fetch_add(1, Ordering::SeqCst)
+        ));
+        let seconds = match entry.timestamp.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs(),
+            Err(_) => 0,
+        };
+        std::fs::write(&temp, format!("{}\n{}", seconds, entry.html))?;
+        std::fs::rename(&temp, &file)
+    }
+
+    //Todo implement methods to get cacheentries and to set cacheentries to encapsulate "refresh_after" into the cacheentries
+    //Implement Out-Of_Date Error to throw if item is outdated
+}
+///The item which is stored in the cache
+//Potentielly mark as private after implementing items above
+pub struct CacheEntry {
+    html: String,
+    timestamp: SystemTime,
+    last_used: u64,
+}
+impl CacheEntry {
+    ///Returns the html source stored in the entry
+    pub fn html(&self) -> &str {
+        &self.html
+    }
+    ///Returns the wall-clock time at which the html source was fetched
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+    ///Returns true if the entry is older than `refresh_after`
+    fn is_outdated(&self, refresh_after: Duration) -> bool {
+        match SystemTime::now().duration_since(self.timestamp) {
+            Ok(age) => age.as_secs() > refresh_after.as_secs(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, CachePolicy, CacheStatistics};
+    #[test]
+    fn test_entries_survive_restart() {
+        let directory = std::env::temp_dir().join("crypto_scrapper_cache_restart");
+        //an entry left by an earlier run would hide a failing write
+        let _ = std::fs::remove_dir_all(&directory);
+        let directory = directory.to_str().unwrap();
+        let mut cache = Cache::with_directory(45, directory);
+        cache.insert("https://example.com/a", &String::from("<p>a</p>"));
+        let mut restarted = Cache::with_directory(45, directory);
+        let entry = restarted.get("https://example.com/a", true).unwrap();
+        assert_eq!(entry.html(), "<p>a</p>");
+    }
+    #[test]
+    fn test_outdated_entry_on_disk() {
+        let directory = std::env::temp_dir().join("crypto_scrapper_cache_outdated");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        //fetched one hour after the unix epoch
+        std::fs::write(directory.join("example.com_b.html"), "3600\n<p>b</p>").unwrap();
+        let mut cache = Cache::with_directory(45, directory.to_str().unwrap());
+        assert!(cache.get("https://example.com/b", true).is_none());
+        assert_eq!(
+            cache.get("https://example.com/b", false).unwrap().html(),
+            "<p>b</p>"
+        );
+    }
+    #[test]
+    fn test_limits_and_statistics() {
+        let mut cache = Cache::new(45);
+        cache.set_limits(Some(2), Some(10));
+        cache.insert("a", &String::from("aaaa"));
+        cache.insert("b", &String::from("bbbb"));
+        assert!(cache.get("a", true).is_some()); //"b" is now the least recently used entry
+        cache.insert("c", &String::from("cccc"));
+        assert!(cache.get("b", true).is_none());
+        cache.insert("d", &String::from("dddddddd")); //exceeds the byte budget together with any other entry
+        assert!(cache.get("a", false).is_none());
+        assert_eq!(
+            cache.statistics(),
+            CacheStatistics {
+                hits: 1,
+                misses: 2,
+                stale: 0,
+                evictions: 3,
+                entries: 1,
+                bytes: 8,
+            }
+        );
+    }
+    #[test]
+    fn test_policies() {
+        let mut cache = Cache::new(45);
+        cache.insert("a", &String::from("a"));
+        assert!(cache.get_with_policy("a", CachePolicy::Never).is_none());
+        assert!(cache.get_with_policy("a", CachePolicy::Forever).is_some());
+        let policy = CachePolicy::from_config(Some(3600), CachePolicy::Never);
+        assert!(cache.get_with_policy("a", policy).is_some());
+        assert_eq!(
+            CachePolicy::from_config(Some(0), CachePolicy::Forever),
+            CachePolicy::Never
+        );
+        assert_eq!(
+            CachePolicy::from_config(None, CachePolicy::Forever),
+            CachePolicy::Forever
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use super::ParseError;
+use async_trait::async_trait;
+use fantoccini::ClientBuilder;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+///A source of html pages. `get_html` asks the `PageFetcher` for every page which is not already in the cache.
+/// Implement this trait to load pages from somewhere else than the internet (e.g. through a proxy or from saved files).
+#[async_trait]
+pub trait PageFetcher: Send + Sync {
+    ///Returns the source code of the html page stored at `url`
+    /// # Arguments
+    /// * `url` - The url of the page to load
+    /// * `script` - If true the page needs javascript to be executed to get its content
+    async fn fetch(&self, url: &str, script: bool) -> Result<String, ParseError>;
+}
+
+///Loads static webpages with a simple reqwest. Javascript is never executed.
+#[derive(Default)]
+pub struct ReqwestFetcher;
+
+#[async_trait]
+impl PageFetcher for ReqwestFetcher {
+    async fn fetch(&self, url: &str, _script: bool) -> Result<String, ParseError> {
+        let transport_error = |e: reqwest::Error| ParseError::Transport {
+            url: String::from(url),
+            status: e.status().map(|s| s.as_u16()),
+            details: e.to_string(),
+        };
+        let response = match reqwest::get(url).await {
+            Ok(r) => r,
+            Err(e) => return Err(transport_error(e)),
+        };
+        let response = match response.error_for_status() {
+            Ok(r) => r,
+            Err(e) => return Err(transport_error(e)),
+        };
+        match response.text().await {
+            Ok(source) => Ok(source),
+            Err(e) => Err(transport_error(e)),
+        }
+    }
+}
+
+///Loads dynamic webpages with a fantoccini Client in combination with a Chromedriver running at `webdriver_url`
+pub struct WebDriverFetcher {
+    webdriver_url: String,
+}
+impl WebDriverFetcher {
+    ///Returns a new `WebDriverFetcher` connecting to the WebDriver at `webdriver_url`
+    /// # Arguments
+    /// * `webdriver_url` - The url of the WebDriver, e.g. "http://localhost:9515"
+    pub fn new(webdriver_url: String) -> WebDriverFetcher {
+        WebDriverFetcher { webdriver_url }
+    }
+}
+impl Default for WebDriverFetcher {
+    fn default() -> Self {
+        WebDriverFetcher::new(String::from("http://localhost:9515"))
+    }
+}
+
+#[async_trait]
+impl PageFetcher for WebDriverFetcher {
+    async fn fetch(&self, url: &str, _script: bool) -> Result<String, ParseError> {
+        let webdriver_error = |details: String| ParseError::WebDriver {
+            url: String::from(url),
+            details,
+        };
+        let mut client = match ClientBuilder::native().connect(&self.webdriver_url).await {
+            Ok(c) => c,
+            Err(e) => return Err(webdriver_error(e.to_string())),
+        };
+        let source = match client.goto(url).await {
+            Ok(_) => client.source().await,
+            Err(e) => Err(e),
+        };
+        //the session stays open in the WebDriver until it is closed, whether the page was loaded or not
+        if let Err(e) = client.close().await {
+            eprintln!("Could not close the WebDriver session: {}", e);
+        }
+        match source {
+            Ok(source) => Ok(source),
+            Err(e) => Err(webdriver_error(e.to_string())),
+        }
+    }
+}
+
+///The default `PageFetcher`. Static pages are loaded with a `ReqwestFetcher`, pages which need javascript
+/// with a `WebDriverFetcher`.
+#[derive(Default)]
+pub struct StandardFetcher {
+    no_script: ReqwestFetcher,
+    with_script: WebDriverFetcher,
+}
+impl StandardFetcher {
+    ///Returns a new `StandardFetcher` which uses the WebDriver at `webdriver_url` for dynamic pages
+    pub fn new(webdriver_url: String) -> StandardFetcher {
+        StandardFetcher {
+            no_script: ReqwestFetcher,
+            with_script: WebDriverFetcher::new(webdriver_url),
+        }
+    }
+}
+
+#[async_trait]
+impl PageFetcher for StandardFetcher {
+    async fn fetch(&self, url: &str, script: bool) -> Result<String, ParseError> {
+        if script {
+            self.with_script.fetch(url, script).await
+        } else {
+            self.no_script.fetch(url, script).await
+        }
+    }
+}
+
+///Serves pages from html files stored on disk, e.g. saved pages like `bitcoin.html`. A page is looked up in the
+/// files registered with `with_page` first, otherwise in `directory` under the name returned by `FileFetcher::file_name`.
+pub struct FileFetcher {
+    directory: PathBuf,
+    pages: HashMap<String, PathBuf>,
+}
+impl FileFetcher {
+    ///Returns a new `FileFetcher` serving the pages stored in `directory`
+    /// # Arguments
+    /// * `directory` - The directory containing the saved pages
+    pub fn new(directory: &str) -> FileFetcher {
+        FileFetcher {
+            directory: PathBuf::from(directory),
+            pages: HashMap::new(),
+        }
+    }
+    ///Registers the file `file` as the source of `url` and returns the `FileFetcher`
+    /// # Arguments
+    /// * `url` - The url of the page
+    /// * `file` - The location of the html file which is returned for `url`
+    pub fn with_page(mut self, url: &str, file: &str) -> FileFetcher {
+        self.pages.insert(String::from(url), PathBuf::from(file));
+        self
+    }
+    ///Returns the name of the file which stores the page of `url`, e.g.
+    /// "coinmarketcap.com_currencies_bitcoin.html" for "https://coinmarketcap.com/currencies/bitcoin/"
+    pub fn file_name(url: &str) -> String {
+        let name = url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_matches('/');
+        let name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}.html", name)
+    }
+}
+
+#[async_trait]
+impl PageFetcher for FileFetcher {
+    async fn fetch(&self, url: &str, _script: bool) -> Result<String, ParseError> {
+        let file = match self.pages.get(url) {
+            Some(f) => f.clone(),
+            None => self.directory.join(FileFetcher::file_name(url)),
+        };
+        match tokio::fs::read_to_string(&file).await {
+            Ok(source) => Ok(source),
+            Err(e) => Err(ParseError::Io {
+                path: file.display().to_string(),
+                details: format!("No saved page for {}: {}", url, e),
+            }),
+        }
+    }
+}
+
+///Returns the name of the file in which a recorded page of `url` is stored. Pages loaded with and without
+/// `script` are stored separately, e.g. "coinmarketcap.com_currencies_bitcoin.script.html".
+fn recorded_file_name(url: &str, script: bool) -> String {
+    let name = FileFetcher::file_name(url);
+    if script {
+        format!("{}.script.html", name.trim_end_matches(".html"))
+    } else {
+        name
+    }
+}
+
+///Wraps another `PageFetcher` and writes every page it loads to `directory`, keyed by url and `script` flag.
+/// The recorded pages can be served again with a `ReplayFetcher`.
+pub struct RecordingFetcher {
+    inner: Arc<dyn PageFetcher>,
+    directory: PathBuf,
+}
+impl RecordingFetcher {
+    ///Returns a new `RecordingFetcher` which loads pages with `inner` and records them in `directory`
+    /// # Arguments
+    /// * `inner` - The `PageFetcher` which actually loads the pages
+    /// * `directory` - The directory in which the pages are recorded. It is created if it does not exist.
+    pub fn new(inner: Arc<dyn PageFetcher>, directory: &str) -> RecordingFetcher {
+        RecordingFetcher {
+            inner,
+            directory: PathBuf::from(directory),
+        }
+    }
+}
+
+#[async_trait]
+impl PageFetcher for RecordingFetcher {
+    async fn fetch(&self, url: &str, script: bool) -> Result<String, ParseError> {
+        let source = self.inner.fetch(url, script).await?;
+        let file = self.directory.join(recorded_file_name(url, script));
+        let written = match tokio::fs::create_dir_all(&self.directory).await {
+            Ok(_) => tokio::fs::write(&file, &source).await,
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(_) => Ok(source),
+            Err(e) => Err(ParseError::Io {
+                path: file.display().to_string(),
+                details: format!("Could not record {}: {}", url, e),
+            }),
+        }
+    }
+}
+
+///Serves only the pages recorded by a `RecordingFetcher` in `directory`. Nothing is loaded from the internet;
+/// a page which was not recorded is an error.
+pub struct ReplayFetcher {
+    directory: PathBuf,
+}
+impl ReplayFetcher {
+    ///Returns a new `ReplayFetcher` serving the pages recorded in `directory`
+    pub fn new(directory: &str) -> ReplayFetcher {
+        ReplayFetcher {
+            directory: PathBuf::from(directory),
+        }
+    }
+}
+
+#[async_trait]
+impl PageFetcher for ReplayFetcher {
+    async fn fetch(&self, url: &str, script: bool) -> Result<String, ParseError> {
+        let file = self.directory.join(recorded_file_name(url, script));
+        match tokio::fs::read_to_string(&file).await {
+            Ok(source) => Ok(source),
+            Err(e) => Err(ParseError::Io {
+                path: file.display().to_string(),
+                details: format!(
+                    "Replay miss: {} (script: {}) was not recorded: {}",
+                    url, script, e
+                ),
+            }),
+        }
+    }
+}
@@ -1,24 +1,32 @@
-use fantoccini::{Client, ClientBuilder};
 use regex::Regex;
 use scraper::{ElementRef, Html, Selector};
-use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+
+pub mod cache;
+pub mod fetcher;
+
+pub use cache::{Cache, CacheEntry, CachePolicy, CacheStatistics};
+pub use fetcher::{
+    FileFetcher, PageFetcher, RecordingFetcher, ReplayFetcher, ReqwestFetcher, StandardFetcher,
+    WebDriverFetcher,
+};
 
 ///Returns the source code of the html file stored at the specified URL. Depending on the parameter `script`
-/// the function is using a simple reqwest to get static content or a fantoccini Client in combination with a Chromediver
-/// running on the system to load dynamic javascript content.Err
+/// the `fetcher` is asked to load a static page or to load dynamic javascript content (e.g. with a
+/// fantoccini Client in combination with a Chromedriver running on the system).
 /// # Arguments
-/// * `script` - If true the function reads dynamic webpages, static otherwise
-/// * `url` - The url of the page to load
-/// * `reload`- If true the url is reloaded if the cache is out od date, otherwise it is returned from the cache if already loaded once
 /// * `cache` - The cache containing already loaded html sources
+/// * `fetcher` - The `PageFetcher` used to load pages which are not in the cache
+/// * `url` - The url of the page to load
+/// * `policy`- Decides if the page can be returned from the cache or has to be reloaded
+/// * `script` - If true the function reads dynamic webpages, static otherwise
 pub async fn get_html(
     cache_rc: Arc<Mutex<Cache>>,
+    fetcher: Arc<dyn PageFetcher>,
     url: &str,
-    reload: bool,
+    policy: CachePolicy,
     script: bool,
 ) -> Result<String, ParseError> {
     //first lets check if we have the source already in the cache if we don't need to reload
@@ -26,89 +34,25 @@ pub async fn get_html(
     //get a lock on the mutex
     {
         let c = Arc::clone(&cache_rc);
-        let cache = c.lock().unwrap();
+        let mut cache = c.lock().unwrap();
         //let entry = cache.get(url);
-        if let Some(entry) = cache.get(url, reload) {
-            return Ok(String::from(&entry.html));
+        if let Some(entry) = cache.get_with_policy(url, policy) {
+            return Ok(String::from(entry.html()));
         }
     }
-    let source: Result<String, ParseError>;
-    if script {
-        let mut client = match ClientBuilder::native()
-            .connect("http://localhost:9515")
-            .await
-        {
-            Ok(c) => c,
-            Err(e) => {
-                println!("{}", e);
-                return Err(ParseError::new(String::from(
-                    "Make sure Chromedriver is started.",
-                )));
-            }
-        };
-        source = get_html_source_with_script(&mut client, url).await;
-    } else {
-        source = get_html_source_no_script(url).await;
-    }
+    let source = fetcher.fetch(url, script).await;
     match source {
         Ok(r) => {
             let c = Arc::clone(&cache_rc);
             let mut cache = c.lock().unwrap();
-            cache.insert(url, &r);
+            if policy != CachePolicy::Never {
+                cache.insert(url, &r);
+            }
             Ok(r)
         }
         Err(e) => return Err(e),
     }
 }
-//     let in_cache = cache.entries.contains_key(url);
-//     if !reload && in_cache || (reload && in_cache) {
-//         let entry = cache.entries.get(url).unwrap();
-//         if !reload || entry.timestamp.elapsed().as_secs() < cache.refresh_after.as_secs() {
-//             println!("Getting value from cache");
-//             return Ok(String::from(&entry.html));
-//         } else {
-//             println!("Outdated value in cache. Reloading value");
-//         }
-//     };
-// };
-
-///Actual function to load a static webpage
-async fn get_html_source_no_script(
-    // cache: Arc<Mutex<Cache>>,
-    url: &str,
-) -> Result<String, ParseError> {
-    let source = reqwest::get(url).await.unwrap().text().await.unwrap();
-    // let c = Arc::clone(&cache);
-    // let mut cache = c.lock().unwrap();
-    // cache.entries.insert(
-    //     String::from(url),
-    //     CacheEntry {
-    //         html: String::from(&source),
-    //         timestamp: Instant::now(),
-    //     },
-    // );
-    Ok(source)
-}
-
-///Actual function to load the dynamic webpage
-async fn get_html_source_with_script(
-    client: &mut Client,
-    // cache: Arc<Mutex<Cache>>,
-    url: &str,
-) -> Result<String, ParseError> {
-    client.goto(url).await.unwrap();
-    let source = client.source().await.unwrap();
-    // let c = Arc::clone(&cache);
-    // let mut cache = c.lock().unwrap();
-    // cache.entries.insert(
-    //     String::from(url),
-    //     CacheEntry {
-    //         html: String::from(&source),
-    //         timestamp: Instant::now(),
-    //     },
-    // );
-    Ok(source)
-}
 
 ///Return the inner html code of a specific tag and its relation in the html tree
 /// # Arguments
@@ -120,144 +64,348 @@ pub fn get_inner_html_from_element(
     source: &str,
     relations: Vec<Vec<ElementRelation>>,
 ) -> Result<Vec<String>, ParseError> {
-    let re = Regex::new(regex).unwrap();
+    let re = match Regex::new(regex) {
+        Ok(re) => re,
+        Err(e) => {
+            return Err(ParseError::InvalidPattern {
+                pattern: String::from(regex),
+                details: e.to_string(),
+            })
+        }
+    };
     let cap = re.captures(source);
     let (tag, attribute) = match cap {
         Some(c) => (String::from(&c[1]), String::from(&c[2])),
         None => {
             // println!("Regex: {}", regex);
             // println!("html: {}", source);
-            return Err(ParseError::new(String::from(
-                "Element not found. Please check manually",
-            )));
+            return Err(ParseError::ElementNotFound {
+                pattern: String::from(regex),
+            });
+        }
+    };
+    let selector_text = format!("{}[{}]", tag, attribute);
+    let selector = match Selector::parse(&selector_text) {
+        Ok(s) => s,
+        Err(e) => {
+            return Err(ParseError::InvalidPattern {
+                pattern: selector_text.clone(),
+                details: format!("{:?}", e),
+            })
         }
     };
-    let selector = Selector::parse(&format!("{}[{}]", tag, attribute)).unwrap();
     let document = Html::parse_document(&source);
-    let r = document.select(&selector).next().unwrap();
+    let r = match document.select(&selector).next() {
+        Some(r) => r,
+        None => {
+            return Err(ParseError::ElementNotFound {
+                pattern: selector_text,
+            })
+        }
+    };
     let mut result: Vec<String> = Vec::new();
     for rel in relations {
-        let inner = navigate_relation(rel, r);
+        let inner = navigate_relation(rel, r)?;
         result.push(inner.inner_html())
     }
     return Ok(result);
 }
 
-///Navigates down a html tree based on the relation passed to the function
-fn navigate_relation(rel: Vec<ElementRelation>, element: scraper::ElementRef) -> ElementRef {
-    let mut result = element.clone();
-    for relation in rel.iter() {
-        result = match relation {
-            ElementRelation::Parent => ElementRef::wrap(result.parent().unwrap()).unwrap(),
+///Returns the inner html of the element found with the path `expression`. A path expression is a CSS selector
+/// followed by optional relation steps separated by `->`, e.g. `h2[id^="what-is-"] -> parent` or
+/// `div[class*="priceTitle__"] > div -> sibling(0)`. The steps `parent`, `child(i)` and `sibling(i)` are applied
+/// like the corresponding `ElementRelation`.
+/// # Arguments
+/// * `expression` - the path expression identifying the element
+/// * `source` - the source html containing the inner html to be returned
+pub fn get_inner_html_from_path(expression: &str, source: &str) -> Result<String, ParseError> {
+    let mut parts = expression.split("->");
+    let selector_text = parts.next().unwrap_or("").trim();
+    let relations = parts
+        .map(|step| parse_relation(step.trim(), expression))
+        .collect::<Result<Vec<ElementRelation>, ParseError>>()?;
+    let selector = match Selector::parse(selector_text) {
+        Ok(s) => s,
+        Err(e) => {
+            return Err(ParseError::InvalidPattern {
+                pattern: String::from(expression),
+                details: format!("{:?}", e),
+            })
+        }
+    };
+    let document = Html::parse_document(source);
+    let element = match document.select(&selector).next() {
+        Some(e) => e,
+        None => {
+            return Err(ParseError::ElementNotFound {
+                pattern: String::from(selector_text),
+            })
+        }
+    };
+    Ok(navigate_relation(relations, element)?.inner_html())
+}
+
+///Parses a relation step of a path expression (`parent`, `child(i)` or `sibling(i)`)
+fn parse_relation(step: &str, expression: &str) -> Result<ElementRelation, ParseError> {
+    let invalid = || ParseError::InvalidPattern {
+        pattern: String::from(expression),
+        details: format!("\"{}\" is not parent, child(i) or sibling(i)", step),
+    };
+    if step == "parent" {
+        return Ok(ElementRelation::Parent);
+    }
+    let index = |prefix: &str| -> Option<i32> {
+        step.strip_prefix(prefix)?
+            .strip_suffix(')')?
+            .trim()
+            .parse::<i32>()
+            .ok()
+    };
+    if let Some(i) = index("child(") {
+        Ok(ElementRelation::Child(i))
+    } else if let Some(i) = index("sibling(") {
+        Ok(ElementRelation::Sibling(i))
+    } else {
+        Err(invalid())
+    }
+}
+
+///Navigates down a html tree based on the relation passed to the function. Only elements are counted by
+/// `Child(i)` and `Sibling(i)`, text, whitespace and comment nodes are skipped.
+/// # Errors
+/// If a step can not be applied a `ParseError::Navigation` with the failing step and the last reached element is returned
+fn navigate_relation(
+    rel: Vec<ElementRelation>,
+    element: scraper::ElementRef,
+) -> Result<ElementRef, ParseError> {
+    let mut result = element;
+    for (step, relation) in rel.iter().enumerate() {
+        let next = match relation {
+            ElementRelation::Parent => result.parent().and_then(ElementRef::wrap),
             ElementRelation::Child(i) => {
+                let mut children = result.children().filter_map(ElementRef::wrap);
                 if *i < 0 {
-                    ElementRef::wrap(result.last_child().unwrap()).unwrap()
-                } else if *i == 0 {
-                    ElementRef::wrap(result.first_child().unwrap()).unwrap()
+                    children.last()
                 } else {
-                    let mut sib = ElementRef::wrap(result.first_child().unwrap()).unwrap();
-                    for _ in 0..*i {
-                        sib = ElementRef::wrap(sib.next_sibling().unwrap()).unwrap();
-                    }
-                    sib //for j in
-                } //if then else i
-            } //ElementRelation::Child
-            ElementRelation::Sibling(i) => {
-                let mut sib = ElementRef::wrap(result.next_sibling().unwrap()).unwrap();
-                for _ in 0..*i {
-                    sib = ElementRef::wrap(sib.next_sibling().unwrap()).unwrap();
+                    children.nth(*i as usize)
                 }
-                sib
-            } //ElementRelation::Sibling
+            } //ElementRelation::Child
+            ElementRelation::Sibling(i) => result
+                .next_siblings()
+                .filter_map(ElementRef::wrap)
+                .nth((*i).max(0) as usize), //ElementRelation::Sibling
         }; //match relation
+        result = match next {
+            Some(e) => e,
+            None => {
+                return Err(ParseError::Navigation {
+                    step,
+                    relation: *relation,
+                    reached: describe_element(result),
+                })
+            }
+        };
     } //for relation
-    result
+    Ok(result)
 } //fn navigate_relation
 
+///Returns the opening tag of `element` including its attributes, e.g. `<div class="price">`
+fn describe_element(element: ElementRef) -> String {
+    let value = element.value();
+    let attributes = value.attrs().fold(String::new(), |mut s, (name, v)| {
+        s.push_str(&format!(" {}=\"{}\"", name, v));
+        s
+    });
+    format!("<{}{}>", value.name(), attributes)
+}
+
 pub struct HtmlParser {
     pub cache: Arc<Mutex<Cache>>,
+    pub fetcher: Arc<dyn PageFetcher>,
 }
 impl HtmlParser {
     pub async fn new(refresh_after: u64) -> Option<HtmlParser> {
-        Some(HtmlParser {
-            cache: Arc::new(Mutex::new(Cache::new(refresh_after))),
-        })
+        HtmlParser::with_fetcher(refresh_after, Arc::new(StandardFetcher::default())).await
     }
-}
-
-///A simple cache to store `ChacheEntries` and a `Duration` after which an existing item is out-dated
-pub struct Cache {
-    entries: HashMap<String, CacheEntry>,
-    refresh_after: Duration,
-}
-impl Cache {
-    pub fn new(refresh_after: u64) -> Cache {
-        Cache {
-            entries: HashMap::new(),
-            refresh_after: Duration::new(refresh_after, 0),
-        }
-    }
-
-    pub fn get(&self, key: &str, reload: bool) -> Option<&CacheEntry> {
-        match self.entries.get(key) {
-            Some(entry) => {
-                if reload && entry.timestamp.elapsed().as_secs() > self.refresh_after.as_secs() {
-                    return None;
-                } else {
-                    return Some(entry);
-                }
-            }
-            None => return None,
-        };
+    ///Returns a new `HtmlParser` which loads the pages missing in its cache with `fetcher`
+    pub async fn with_fetcher(
+        refresh_after: u64,
+        fetcher: Arc<dyn PageFetcher>,
+    ) -> Option<HtmlParser> {
+        HtmlParser::with_cache(Cache::new(refresh_after), fetcher).await
     }
-
-    pub fn insert(&mut self, key: &str, value: &String) {
-        self.entries.insert(
-            String::from(key),
-            CacheEntry {
-                html: String::from(value),
-                timestamp: Instant::now(),
-            },
-        );
+    ///Returns a new `HtmlParser` using `cache` which loads the pages missing in the cache with `fetcher`
+    pub async fn with_cache(cache: Cache, fetcher: Arc<dyn PageFetcher>) -> Option<HtmlParser> {
+        Some(HtmlParser {
+            cache: Arc::new(Mutex::new(cache)),
+            fetcher,
+        })
     }
-
-    //Todo implement methods to get cacheentries and to set cacheentries to encapsulate "refresh_after" into the cacheentries
-    //Implement Out-Of_Date Error to throw if item is outdated
-}
-///The item which is stored in the cache
-//Potentielly mark as private after implementing items above
-pub struct CacheEntry {
-    html: String,
-    timestamp: Instant,
 }
 
 ///Enumaration to represent the relation inside a html tree
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ElementRelation {
     Parent,
     Child(i32),
     Sibling(i32),
 }
 
-pub struct ParseError {
-    details: String,
+///The errors which can occur while loading and scraping a page.
+/// `Transport` and `WebDriver` errors are usually temporary, the other variants mostly mean that the layout of the
+/// page or the configuration does not match anymore (see `is_transient` and `is_layout_change`).
+#[derive(Clone, Debug)]
+pub enum ParseError {
+    ///The page at `url` could not be loaded. `status` holds the HTTP status if the server answered with an error.
+    Transport {
+        url: String,
+        status: Option<u16>,
+        details: String,
+    },
+    ///The WebDriver is not available or failed to load the page at `url`
+    WebDriver { url: String, details: String },
+    ///The regex or selector `pattern` did not match any element of the page
+    ElementNotFound { pattern: String },
+    ///The regex or selector `pattern` taken from the configuration is invalid
+    InvalidPattern { pattern: String, details: String },
+    ///Navigating along a `Vec<ElementRelation>` failed at `step` (index into the vector) applying `relation`.
+    /// `reached` describes the last node which was reached.
+    Navigation {
+        step: usize,
+        relation: ElementRelation,
+        reached: String,
+    },
+    ///The scraped `text` could not be parsed as a number
+    NumberParse { text: String },
+    ///The data embedded in the page is not valid JSON
+    Json { details: String },
+    ///Reading or writing the file `path` failed (e.g. a saved page which does not exist)
+    Io { path: String, details: String },
+    ///No exchange rate into the configured quote `currency` is known
+    UnknownCurrency { currency: String },
+    ///The task scraping the page of `symbol` panicked or was cancelled
+    Task { symbol: String, details: String },
 }
 impl ParseError {
-    pub fn new(details: String) -> ParseError {
-        ParseError { details }
+    ///Returns true if the error is probably temporary and the request can be retried
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ParseError::Transport { status, .. } => match status {
+                Some(s) => *s == 429 || *s >= 500,
+                None => true,
+            },
+            ParseError::WebDriver { .. } => true,
+            _ => false,
+        }
+    }
+    ///Returns true if the error means that the page does not look like expected anymore
+    pub fn is_layout_change(&self) -> bool {
+        matches!(
+            self,
+            ParseError::ElementNotFound { .. }
+                | ParseError::Navigation { .. }
+                | ParseError::NumberParse { .. }
+                | ParseError::Json { .. }
+        )
     }
 }
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+        match self {
+            ParseError::Transport {
+                url,
+                status: Some(status),
+                ..
+            } => write!(f, "Loading {} failed with HTTP status {}", url, status),
+            ParseError::Transport { url, details, .. } => {
+                write!(f, "Could not load {}: {}", url, details)
+            }
+            ParseError::WebDriver { url, details } => write!(
+                f,
+                "Could not load {} with the WebDriver: {}. Make sure Chromedriver is started.",
+                url, details
+            ),
+            ParseError::ElementNotFound { pattern } => write!(
+                f,
+                "Element not found with \"{}\". Please check manually",
+                pattern
+            ),
+            ParseError::InvalidPattern { pattern, details } => {
+                write!(f, "Invalid pattern \"{}\": {}", pattern, details)
+            }
+            ParseError::Navigation {
+                step,
+                relation,
+                reached,
+            } => write!(
+                f,
+                "Navigation failed at step {} ({:?}), reached {}",
+                step, relation, reached
+            ),
+            ParseError::NumberParse { text } => write!(f, "\"{}\" is not a number", text),
+            ParseError::Json { details } => write!(f, "Invalid JSON data in page: {}", details),
+            ParseError::Io { path, details } => write!(f, "{}: {}", path, details),
+            ParseError::UnknownCurrency { currency } => {
+                write!(f, "No exchange rate from USD to {} available", currency)
+            }
+            ParseError::Task { symbol, details } => {
+                write!(f, "Scraping {} failed unexpectedly: {}", symbol, details)
+            }
+        }
     }
 }
-impl fmt::Debug for ParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::ElementRelation::{Child, Parent, Sibling};
+    use super::{get_inner_html_from_element, get_inner_html_from_path, ParseError};
+    const HTML: &str =
+        "<div class=\"table\">\n  <p>one</p>\n  text <!-- comment -->\n  <p>two</p>\n</div>";
+    const REGEX: &str = r#"<(div) (class="table")>"#;
+    #[test]
+    fn test_navigation_skips_text_nodes() {
+        let result = get_inner_html_from_element(
+            REGEX,
+            HTML,
+            vec![vec![Child(1)], vec![Child(-1)], vec![Child(0), Sibling(0)]],
+        )
+        .unwrap();
+        assert_eq!(result, vec!["two", "two", "two"]);
     }
-}
-impl Error for ParseError {
-    fn description(&self) -> &str {
-        &self.details
+    #[test]
+    fn test_navigation_error() {
+        let result = get_inner_html_from_element(REGEX, HTML, vec![vec![Child(0), Child(0)]]);
+        match result {
+            Err(ParseError::Navigation {
+                step,
+                relation,
+                reached,
+            }) => {
+                assert_eq!(step, 1);
+                assert_eq!(relation, Child(0));
+                assert_eq!(reached, "<p>");
+            }
+            _ => assert!(false, "expected a navigation error"),
+        }
+        let result = get_inner_html_from_element(REGEX, HTML, vec![vec![Parent, Parent, Parent]]);
+        assert!(result.is_err());
+    }
+    #[test]
+    fn test_path_expressions() {
+        let result = get_inner_html_from_path("div.table > p:nth-child(2)", HTML).unwrap();
+        assert_eq!(result, "two");
+        let result = get_inner_html_from_path("div.table > p -> sibling(0)", HTML).unwrap();
+        assert_eq!(result, "two");
+        let result = get_inner_html_from_path("p -> parent -> child( -1 )", HTML).unwrap();
+        assert_eq!(result, "two");
+        match get_inner_html_from_path("p -> up", HTML) {
+            Err(ParseError::InvalidPattern { .. }) => {}
+            _ => assert!(false, "expected an invalid pattern"),
+        }
+        match get_inner_html_from_path("table > tr", HTML) {
+            Err(ParseError::ElementNotFound { pattern }) => assert_eq!(pattern, "table > tr"),
+            _ => assert!(false, "expected element not found"),
+        }
     }
 }
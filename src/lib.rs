@@ -1,108 +1,277 @@
+pub mod alert;
 pub mod config;
+pub mod daemon;
+pub mod fx;
 pub mod html;
+pub mod metrics;
+pub mod money;
+pub mod next_data;
+pub mod output;
+pub mod server;
+pub mod spreadsheet;
+pub mod store;
 
-use config::ConfigObject;
+use alert::{AlertEngine, AlertSink, StderrSink};
+use config::{ConfigObject, Extractor, RoundingRule, StatisticsPatterns};
 use html::ElementRelation::Child;
-use html::{ElementRelation, HtmlParser, ParseError};
+use html::{
+    Cache, CachePolicy, CacheStatistics, ElementRelation, HtmlParser, PageFetcher, ParseError,
+    RecordingFetcher, ReplayFetcher, StandardFetcher,
+};
+use metrics::{Metrics, TimedFetcher};
+use money::Decimal;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::Display;
 use std::io;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use store::{PriceSnapshot, Store, StoreError};
 
-///Structure of the result of a price query
+///Structure of the result of a price query. The statistics after `currency` are `None` if the page does not
+/// show them.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct PriceResult {
     symbol: String,
-    price: f64,
+    price: Decimal,
     change: f64,
+    ///ISO 4217 code of the currency `price`, the market caps and the volume are quoted in
+    currency: String,
+    market_cap: Option<Decimal>,
+    fully_diluted_market_cap: Option<Decimal>,
+    volume_24h: Option<Decimal>,
+    ///Position of the currency on CoinMarketCap, ranked by market cap
+    rank: Option<u32>,
+    circulating_supply: Option<Decimal>,
+    total_supply: Option<Decimal>,
+    max_supply: Option<Decimal>,
+    change_1h: Option<f64>,
+    change_7d: Option<f64>,
 }
 
 impl Display for PriceResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {} ({})", self.symbol, self.price, self.change)
+        write!(
+            f,
+            "{}: {} {} ({})",
+            self.symbol, self.price, self.currency, self.change
+        )
     }
 }
 impl PriceResult {
-    pub fn to_string(&self) -> String {
-        String::from(format!("{}\t{}", self.symbol, self.price))
+    ///Returns the symbol the price was scraped for
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+    ///Returns the price in `currency`
+    pub fn price(&self) -> Decimal {
+        self.price
+    }
+    ///Returns the change of the price within 24 hours in percent
+    pub fn change(&self) -> f64 {
+        self.change
+    }
+    ///Returns the ISO 4217 code of the currency the price is quoted in, e.g. "USD"
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+    ///Returns the market capitalization in `currency`
+    pub fn market_cap(&self) -> Option<Decimal> {
+        self.market_cap
+    }
+    ///Returns the market capitalization in `currency` if the maximum supply was in circulation
+    pub fn fully_diluted_market_cap(&self) -> Option<Decimal> {
+        self.fully_diluted_market_cap
+    }
+    ///Returns the traded volume within 24 hours in `currency`
+    pub fn volume_24h(&self) -> Option<Decimal> {
+        self.volume_24h
+    }
+    ///Returns the position of the currency on CoinMarketCap, ranked by market cap
+    pub fn rank(&self) -> Option<u32> {
+        self.rank
+    }
+    ///Returns the number of coins in circulation
+    pub fn circulating_supply(&self) -> Option<Decimal> {
+        self.circulating_supply
+    }
+    ///Returns the number of coins in existence
+    pub fn total_supply(&self) -> Option<Decimal> {
+        self.total_supply
+    }
+    ///Returns the maximum number of coins which will ever exist
+    pub fn max_supply(&self) -> Option<Decimal> {
+        self.max_supply
+    }
+    ///Returns the change of the price within 1 hour in percent
+    pub fn change_1h(&self) -> Option<f64> {
+        self.change_1h
+    }
+    ///Returns the change of the price within 7 days in percent
+    pub fn change_7d(&self) -> Option<f64> {
+        self.change_7d
+    }
+    ///Returns the USD price, market caps and volume converted into `currency` with `rate` units of `currency`
+    /// per USD. The changes stay the ones of the USD price.
+    fn in_currency(self, currency: &str, rate: Decimal) -> PriceResult {
+        PriceResult {
+            price: self.price * rate,
+            currency: String::from(currency),
+            market_cap: self.market_cap.map(|v| v * rate),
+            fully_diluted_market_cap: self.fully_diluted_market_cap.map(|v| v * rate),
+            volume_24h: self.volume_24h.map(|v| v * rate),
+            ..self
+        }
+    }
+    ///Returns the price, market caps and volume rounded by `rule`, or unchanged if there is no rule for the output
+    pub fn rounded(&self, rule: Option<&RoundingRule>) -> PriceResult {
+        match rule {
+            Some(r) => PriceResult {
+                price: self.price.round(r.places, r.mode),
+                market_cap: self.market_cap.map(|v| v.round(r.places, r.mode)),
+                fully_diluted_market_cap: self
+                    .fully_diluted_market_cap
+                    .map(|v| v.round(r.places, r.mode)),
+                volume_24h: self.volume_24h.map(|v| v.round(r.places, r.mode)),
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+}
+///Result of a price query for several symbols. Holds the `PriceResult` or the error of every symbol in the
+/// order in which the symbols were queried, so that one failing symbol does not hide the prices of the others.
+pub struct PriceBatch {
+    results: Vec<(String, Result<PriceResult, ParseError>)>,
+}
+impl PriceBatch {
+    ///Returns the symbols together with their result in the order of the query
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, Result<PriceResult, ParseError>)> {
+        self.results.iter()
+    }
+    ///Returns the successfully queried prices in the order of the query
+    pub fn prices(&self) -> impl Iterator<Item = &PriceResult> {
+        self.results.iter().filter_map(|(_, r)| r.as_ref().ok())
+    }
+    ///Returns the symbols which could not be queried together with their error
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &ParseError)> {
+        self.results
+            .iter()
+            .filter_map(|(s, r)| r.as_ref().err().map(|e| (s.as_str(), e)))
+    }
+    ///Returns true if the prices of all symbols could be queried
+    pub fn is_complete(&self) -> bool {
+        self.errors().next().is_none()
+    }
+    ///Returns the number of queried symbols
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+    ///Returns true if no symbol was queried
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
     }
 }
+impl IntoIterator for PriceBatch {
+    type Item = (String, Result<PriceResult, ParseError>);
+    type IntoIter = std::vec::IntoIter<(String, Result<PriceResult, ParseError>)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.into_iter()
+    }
+}
+
 ///Structure of the result of a Market query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct MarketResult {
     source: String,
     pair: String,
-    price: f64,
-    volume: f64,
+    price: Decimal,
+    volume: Decimal,
     volume_percent: f64,
+    ///ISO 4217 code of the currency `price` and `volume` are quoted in
+    currency: String,
 }
 
 impl MarketResult {
-    ///Returns the volume in USD
-    pub fn get_volume_in_dollars(&self) -> f64 {
-        if self.price == 0.0 || self.volume == 0.0 {
-            0.0
-        } else {
-            self.volume / self.price
+    ///Returns the ISO 4217 code of the currency price and volume are quoted in, e.g. "USD"
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+    ///Returns the USD price and volume converted into `currency` with `rate` units of `currency` per USD
+    fn in_currency(self, currency: &str, rate: Decimal) -> MarketResult {
+        MarketResult {
+            price: self.price * rate,
+            volume: self.volume * rate,
+            currency: String::from(currency),
+            ..self
         }
     }
-    ///Creates a string of length `i`
-    fn get_spaces(i: usize) -> String {
-        let mut temp = String::new();
-        for _ in 0..i {
-            temp.push_str(" ");
+    ///Returns price and volume rounded by `rule`, or unchanged if there is no rule for the output
+    pub fn rounded(&self, rule: Option<&RoundingRule>) -> MarketResult {
+        match rule {
+            Some(r) => MarketResult {
+                price: self.price.round(r.places, r.mode),
+                volume: self.volume.round(r.places, r.mode),
+                ..self.clone()
+            },
+            None => self.clone(),
         }
-        String::from(&temp)
+    }
+    ///Returns the volume in USD, or None if the price is zero or the quotient does not fit into a `Decimal`
+    pub fn get_volume_in_dollars(&self) -> Option<Decimal> {
+        self.volume.checked_div(self.price, money::MAX_SCALE)
+    }
+    ///Returns `text` padded with spaces to the width of a table column. Text which is wider than the column is
+    /// still followed by one space so that neighbouring columns never run into each other.
+    fn pad(text: &str) -> String {
+        let column_width: usize = 15;
+        let spaces = column_width.saturating_sub(text.chars().count()).max(1);
+        format!("{}{}", text, " ".repeat(spaces))
     }
 
     ///Returns the header of the MarketResult table.
     pub fn get_header() -> String {
-        let column_widht = 15;
-        let source = String::from("Source");
-        let mut result = String::from(&source);
-        result.push_str(&MarketResult::get_spaces(column_widht - source.len()));
-        let pair = String::from("Pair");
-        result.push_str(&pair);
-        result.push_str(&MarketResult::get_spaces(column_widht - pair.len()));
-        let price = String::from("Price");
-        result.push_str(&price);
-        result.push_str(&MarketResult::get_spaces(column_widht - price.len()));
-        let volume = String::from("Volume");
-        result.push_str(&volume);
-        result.push_str(&MarketResult::get_spaces(column_widht - volume.len()));
-        let volume_percent = String::from("Volume %");
-        result.push_str(&volume_percent);
-        result.push_str(&MarketResult::get_spaces(
-            column_widht - volume_percent.len(),
-        ));
-        return result;
+        ["Source", "Pair", "Price", "Volume", "Volume %", "Currency"]
+            .iter()
+            .map(|h| MarketResult::pad(h))
+            .collect()
     }
 }
 ///Implements `Display` for Marketresult. The result is formated in a table retured as a string.
 impl Display for MarketResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let column_widht = 15;
-        let mut result = String::from(&self.source);
-        result.push_str(&MarketResult::get_spaces(column_widht - self.source.len()));
-        result.push_str(&self.pair);
-        result.push_str(&MarketResult::get_spaces(column_widht - self.pair.len()));
-        let price = format!("{}", (self.price));
-        result.push_str(&price);
-        result.push_str(&MarketResult::get_spaces(column_widht - price.len()));
-        let vol = format!("{}", (self.volume));
-        result.push_str(&vol);
-        result.push_str(&MarketResult::get_spaces(column_widht - vol.len()));
-        let vol_per = format!("{}", (self.volume_percent));
-        result.push_str(&vol_per);
+        let mut result = MarketResult::pad(&self.source);
+        result.push_str(&MarketResult::pad(&self.pair));
+        result.push_str(&MarketResult::pad(&self.price.to_string()));
+        result.push_str(&MarketResult::pad(&self.volume.to_string()));
+        result.push_str(&MarketResult::pad(&self.volume_percent.to_string()));
+        result.push_str(&self.currency);
         write!(f, "{}", result)
     }
 }
 
+///Parses `number`, e.g. as `Decimal` or f64. `raw` is the scraped text the number was taken from and is reported
+/// in the error.
+fn parse_number<T: FromStr>(number: &str, raw: &str) -> Result<T, ParseError> {
+    match number.parse::<T>() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(ParseError::NumberParse {
+            text: String::from(raw),
+        }),
+    }
+}
+
 ///The structure to scrape Coinmarektcap.com. We store a `ConfigObject`, a `HtmlParser` and the `tokio Runtime`.
 /// The structure and its methods encapsulate the asyn nature of the used code.
 pub struct CoinMarketCapScrapper {
     pub cfg: ConfigObject,
     html_parser: HtmlParser,
     runtime: tokio::runtime::Runtime,
+    store: Option<Store>,
+    alerts: Mutex<AlertEngine>,
+    metrics: Arc<Metrics>,
 }
 
 impl CoinMarketCapScrapper {
@@ -116,14 +285,214 @@ impl CoinMarketCapScrapper {
     ///
     ///
     pub fn new(config_file_location: String) -> Result<CoinMarketCapScrapper, io::Error> {
+        CoinMarketCapScrapper::with_fetcher(
+            config_file_location,
+            Arc::new(StandardFetcher::default()),
+        )
+    }
+
+    ///Returns a new `CoinMarketCapScrapper` based on the `config_file_location` which loads all pages with `fetcher`.
+    /// # Arguments
+    /// * `config_file_location` - A String that holds the location of the configuration file
+    /// * `fetcher` - The `PageFetcher` used to load the pages from CoinMarketCap, e.g. a `FileFetcher` serving saved pages
+    /// # Errors
+    ///
+    /// If the config is not available or another io error occurs an error is returned
+    pub fn with_fetcher(
+        config_file_location: String,
+        fetcher: Arc<dyn PageFetcher>,
+    ) -> Result<CoinMarketCapScrapper, io::Error> {
         let rt = tokio::runtime::Runtime::new().unwrap();
+        let cfg = ConfigObject::new(config_file_location)?;
+        let metrics = Arc::new(Metrics::default());
+        let fetcher: Arc<dyn PageFetcher> =
+            Arc::new(TimedFetcher::new(fetcher, Arc::clone(&metrics)));
+        let mut cache = match &cfg.configuration.cache_directory {
+            Some(directory) => Cache::with_directory(45, directory),
+            None => Cache::new(45),
+        };
+        cache.set_limits(
+            cfg.configuration.cache_max_entries,
+            cfg.configuration.cache_max_bytes,
+        );
+        let store = match &cfg.configuration.store {
+            Some(path) => match Store::open(path) {
+                Ok(s) => Some(s),
+                Err(e) => return Err(io::Error::other(e.to_string())),
+            },
+            None => None,
+        };
+        let mut alerts = AlertEngine::new(cfg.configuration.alerts.clone().unwrap_or_default());
+        alerts.add_sink(Box::new(StderrSink));
+        for notify in cfg.configuration.notify.iter().flatten() {
+            alerts.add_sink(alert::sinks::from_config(notify));
+        }
+        alerts.set_notify_failures(cfg.configuration.notify_failures.unwrap_or(false));
         Ok(CoinMarketCapScrapper {
-            cfg: ConfigObject::new(config_file_location)?,
-            html_parser: rt.block_on(async { HtmlParser::new(45).await }).unwrap(),
+            cfg,
+            html_parser: rt
+                .block_on(async { HtmlParser::with_cache(cache, fetcher).await })
+                .unwrap(),
             runtime: rt,
+            store,
+            alerts: Mutex::new(alerts),
+            metrics,
         })
     }
 
+    ///Returns a new `CoinMarketCapScrapper` which writes every page it loads to `directory`, so that the
+    /// pages can be replayed later with `CoinMarketCapScrapper::replaying`.
+    /// # Arguments
+    /// * `config_file_location` - A String that holds the location of the configuration file
+    /// * `directory` - The directory in which the loaded pages are recorded
+    /// # Errors
+    ///
+    /// If the config is not available or another io error occurs an error is returned
+    pub fn recording(
+        config_file_location: String,
+        directory: &str,
+    ) -> Result<CoinMarketCapScrapper, io::Error> {
+        let fetcher = RecordingFetcher::new(Arc::new(StandardFetcher::default()), directory);
+        CoinMarketCapScrapper::with_fetcher(config_file_location, Arc::new(fetcher))
+    }
+
+    ///Returns a new `CoinMarketCapScrapper` which serves pages only from the recordings in `directory`.
+    /// Every page which was not recorded results in an error.
+    /// # Arguments
+    /// * `config_file_location` - A String that holds the location of the configuration file
+    /// * `directory` - The directory containing the pages recorded by `CoinMarketCapScrapper::recording`
+    /// # Errors
+    ///
+    /// If the config is not available or another io error occurs an error is returned
+    pub fn replaying(
+        config_file_location: String,
+        directory: &str,
+    ) -> Result<CoinMarketCapScrapper, io::Error> {
+        CoinMarketCapScrapper::with_fetcher(
+            config_file_location,
+            Arc::new(ReplayFetcher::new(directory)),
+        )
+    }
+
+    ///Adds `sink` to the sinks the alerts of the rules in the config are delivered to. Alerts are printed to
+    /// stderr by default.
+    pub fn add_alert_sink(&self, sink: Box<dyn AlertSink>) {
+        self.alerts.lock().unwrap().add_sink(sink);
+    }
+
+    ///Returns the prices of the last scrapes, the fetch latencies, the parse failures and the cache statistics
+    /// in the Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        self.metrics.render(&self.cache_statistics())
+    }
+
+    ///Returns which part of a price page `error` occurred in, "percentage" or "price"
+    fn price_failure_extractor(&self, error: &ParseError) -> &'static str {
+        let config = &self.cfg.configuration;
+        match error {
+            ParseError::ElementNotFound { pattern }
+            | ParseError::InvalidPattern { pattern, .. }
+                if *pattern == config.price_percentage_regex
+                    || *pattern == config.selectors.price_change =>
+            {
+                "percentage"
+            }
+            //the markup extractor reaches the percentage in the second step
+            ParseError::Navigation { step: 1, .. } => "percentage",
+            _ => "price",
+        }
+    }
+
+    ///Returns the ISO 4217 code of the currency prices and markets are quoted in, see `currency` in the config
+    pub fn currency(&self) -> String {
+        match &self.cfg.configuration.currency {
+            Some(c) => c.trim().to_uppercase(),
+            None => String::from(fx::USD),
+        }
+    }
+    ///Returns the units of `currency` per USD. The rate is taken from `fx_rate` in the config or from the
+    /// reference rates of the ECB, which are cached like a page.
+    /// # Errors
+    /// If the reference rates can not be loaded or hold no rate for `currency` an error is returned
+    async fn fx_rate(&self, currency: &str) -> Result<Decimal, ParseError> {
+        if currency == fx::USD {
+            return Ok(Decimal::ONE);
+        }
+        if let Some(rate) = self.cfg.configuration.fx_rate {
+            return match Decimal::from_f64(rate) {
+                Some(r) => Ok(r),
+                None => Err(ParseError::NumberParse {
+                    text: rate.to_string(),
+                }),
+            };
+        }
+        let xml = html::get_html(
+            Arc::clone(&self.html_parser.cache),
+            Arc::clone(&self.html_parser.fetcher),
+            fx::ECB_RATES_URL,
+            CachePolicy::RefreshAfter(Duration::from_secs(fx::RATES_REFRESH_AFTER)),
+            false,
+        )
+        .await?;
+        fx::usd_rate(&fx::parse_ecb_rates(&xml)?, currency)
+    }
+
+    ///Returns the hit/miss/stale/eviction counters and the current size of the page cache
+    pub fn cache_statistics(&self) -> CacheStatistics {
+        self.html_parser.cache.lock().unwrap().statistics()
+    }
+
+    ///Returns the source recorded with the snapshots in the store, e.g. "coinmarketcap.com/markup"
+    fn source(&self) -> String {
+        format!("coinmarketcap.com/{}", self.cfg.configuration.extractor)
+    }
+    ///Returns the configured store
+    fn store(&self) -> Result<&Store, StoreError> {
+        match &self.store {
+            Some(s) => Ok(s),
+            None => Err(StoreError::NotConfigured),
+        }
+    }
+    ///Returns the price of `symbol` at `time`, i.e. the last price recorded at or before `time`.
+    /// Prices are recorded by `get_prices` if `store` is set in the config.
+    /// # Errors
+    /// If no store is configured or it can not be read an error is returned
+    pub fn price_at(
+        &self,
+        symbol: &str,
+        time: SystemTime,
+    ) -> Result<Option<PriceSnapshot>, StoreError> {
+        self.store()?.price_at(symbol, time)
+    }
+    ///Returns the prices of `symbol` recorded between `from` and `to`, oldest first
+    /// # Errors
+    /// If no store is configured or it can not be read an error is returned
+    pub fn price_range(
+        &self,
+        symbol: &str,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<Vec<PriceSnapshot>, StoreError> {
+        self.store()?.price_range(symbol, from, to)
+    }
+    ///Returns the last `n` prices recorded for `symbol`, newest first
+    /// # Errors
+    /// If no store is configured or it can not be read an error is returned
+    pub fn latest_prices(&self, symbol: &str, n: u32) -> Result<Vec<PriceSnapshot>, StoreError> {
+        self.store()?.latest_prices(symbol, n)
+    }
+    ///Returns the markets of `symbol` recorded between `from` and `to`, oldest first
+    /// # Errors
+    /// If no store is configured or it can not be read an error is returned
+    pub fn market_range(
+        &self,
+        symbol: &str,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<Vec<store::MarketSnapshot>, StoreError> {
+        self.store()?.market_range(symbol, from, to)
+    }
+
     ///Returns the detailed description of the currency `symbol`. The content of the result is taken from the "What is" section
     /// on CoinMarketcap.com. If "What is" is not available it returns the "Live Price Data section"
     /// # Arguments
@@ -132,12 +501,49 @@ impl CoinMarketCapScrapper {
     /// If there is a parse error or chromedriver is not available but needed an error is returned
     ///
     pub fn get_details(&mut self, symbol: &str) -> Result<String, ParseError> {
+        match self.scrape_details(symbol) {
+            Ok(details) => {
+                self.metrics
+                    .record_success("details", symbol, SystemTime::now());
+                Ok(details)
+            }
+            Err(e) => {
+                self.metrics.record_failure("details", &e);
+                Err(e)
+            }
+        }
+    }
+    fn scrape_details(&mut self, symbol: &str) -> Result<String, ParseError> {
         let url = format!("https://coinmarketcap.com/currencies/{}/", symbol);
         let c = Arc::clone(&self.html_parser.cache);
-        let html = match self.runtime.block_on(html::get_html(c, &url, false, true)) {
+        let f = Arc::clone(&self.html_parser.fetcher);
+        let policy = CachePolicy::from_config(
+            self.cfg.configuration.details_refresh_after,
+            CachePolicy::Forever,
+        );
+        let html = match self
+            .runtime
+            .block_on(html::get_html(c, f, &url, policy, true))
+        {
             Ok(html) => html,
             Err(err) => return Err(err),
         };
+        match self.cfg.configuration.extractor {
+            Extractor::NextData => return next_data::parse_details(&html),
+            Extractor::Selectors => {
+                let mut result = Err(ParseError::ElementNotFound {
+                    pattern: String::from("selectors.details"),
+                });
+                for path in &self.cfg.configuration.selectors.details {
+                    result = html::get_inner_html_from_path(path, &html);
+                    if result.is_ok() {
+                        break;
+                    }
+                }
+                return Ok(self.cleanup_result_string(result?));
+            }
+            Extractor::Markup => {}
+        }
         let mut what_is_inner = match html::get_inner_html_from_element(
             &self.cfg.configuration.what_is_regex,
             &html,
@@ -192,51 +598,119 @@ impl CoinMarketCapScrapper {
         String::from(text)
     }
 
-    ///Returns all prices as a `PriceResult`Vector of the symbols stored in teh configuration file.
-    /// # Errors
-    /// If there is a parse error or chromedriver is not available but needed an error is returned
-    pub fn get_all_prices(&self) -> Result<Vec<PriceResult>, ParseError> {
+    ///Returns the prices of the symbols stored in the configuration file as a `PriceBatch`.
+    /// Symbols whose price could not be queried are reported with their error inside of the batch.
+    pub fn get_all_prices(&self) -> PriceBatch {
         let s = &self.cfg.configuration.symbols;
         self.get_prices(s)
     }
-    ///Returns all prices as a `PriceResult`Vector of the symbols stored in the `symbols` Vector passed to the function
+    ///Returns the prices of the symbols stored in the `symbols` Vector passed to the function as a `PriceBatch`.
+    /// The batch holds a `PriceResult` or the error (e.g. a parse error or chromedriver not available) of every
+    /// symbol in the order of `symbols`.
     /// # Arguments
     /// * `symbols`- A vector containing the symbols for which the price should be returned
-    /// # Errors
-    /// If there is a parse error or chromedriver is not available but needed an error is returned
-    pub fn get_prices(&self, symbols: &Vec<String>) -> Result<Vec<PriceResult>, ParseError> {
+    pub fn get_prices(&self, symbols: &Vec<String>) -> PriceBatch {
+        self.runtime.block_on(self.fetch_prices(symbols))
+    }
+    ///Async version of `get_prices` for code which already runs on the runtime of the scrapper
+    async fn fetch_prices(&self, symbols: &[String]) -> PriceBatch {
         let price_regex = Arc::new(String::from(&self.cfg.configuration.price_regex));
         let price_per_regex =
             Arc::new(String::from(&self.cfg.configuration.price_percentage_regex));
-        let x = self.runtime.block_on(async {
-            let mut handle_vector = Vec::new();
-            let mut result = Vec::new();
-            {
-                for s in symbols {
-                    let cache = Arc::clone(&self.html_parser.cache);
-                    let price_regex = Arc::clone(&price_regex);
-                    let price_per_regex = Arc::clone(&price_per_regex);
-                    let symbol = String::from(s);
-                    let r = tokio::spawn(async move {
-                        let url = format!("https://coinmarketcap.com/currencies/{}/", symbol);
-                        let html = html::get_html(cache, &url, true, false).await.unwrap();
-                        CoinMarketCapScrapper::parse_price(
-                            html,
+        let policy = CachePolicy::from_config(
+            self.cfg.configuration.price_refresh_after,
+            CachePolicy::RefreshAfter(Duration::new(45, 0)),
+        );
+        let extractor = self.cfg.configuration.extractor;
+        let selectors = Arc::new((
+            String::from(&self.cfg.configuration.selectors.price),
+            String::from(&self.cfg.configuration.selectors.price_change),
+        ));
+        let statistics = Arc::new(self.cfg.configuration.statistics.clone());
+        let mut handle_vector = Vec::new();
+        let mut results = Vec::new();
+        {
+            for s in symbols {
+                let cache = Arc::clone(&self.html_parser.cache);
+                let fetcher = Arc::clone(&self.html_parser.fetcher);
+                let price_regex = Arc::clone(&price_regex);
+                let price_per_regex = Arc::clone(&price_per_regex);
+                let selectors = Arc::clone(&selectors);
+                let statistics = Arc::clone(&statistics);
+                let symbol = String::from(s);
+                let r = tokio::spawn(async move {
+                    let url = format!("https://coinmarketcap.com/currencies/{}/", symbol);
+                    let html = html::get_html(cache, fetcher, &url, policy, false).await?;
+                    let price = match extractor {
+                        Extractor::Markup => CoinMarketCapScrapper::parse_price(
+                            &html,
                             symbol,
                             price_regex,
                             price_per_regex,
-                        )
-                        .unwrap()
-                    });
-                    handle_vector.push(r);
-                }
+                        )?,
+                        Extractor::NextData => return next_data::parse_price(&html, symbol),
+                        Extractor::Selectors => CoinMarketCapScrapper::parse_price_with_selectors(
+                            &html,
+                            symbol,
+                            &selectors.0,
+                            &selectors.1,
+                            &price_per_regex,
+                        )?,
+                    };
+                    CoinMarketCapScrapper::parse_statistics(price, &html, &statistics)
+                });
+                handle_vector.push(r);
             }
-            for h in handle_vector {
-                result.push(h.await.unwrap());
+        }
+        for (s, h) in symbols.iter().zip(handle_vector) {
+            //a panic while scraping one symbol must not take the results of the others with it
+            let result = match h.await {
+                Ok(r) => r,
+                Err(e) => Err(ParseError::Task {
+                    symbol: String::from(s),
+                    details: e.to_string(),
+                }),
+            };
+            results.push((String::from(s), result));
+        }
+        let usd = PriceBatch { results };
+        let now = SystemTime::now();
+        {
+            let mut alerts = self.alerts.lock().unwrap();
+            if !alerts.is_empty() {
+                //the thresholds of the rules are in USD, so they are checked before the conversion into
+                // `currency`. The configured sinks deliver on their own threads, but sinks added with
+                // `add_alert_sink` may block.
+                tokio::task::block_in_place(|| alerts.check_batch(&usd, now));
+            }
+        }
+        //CoinMarketCap quotes in USD, a price is never returned without its conversion into `currency`
+        let currency = self.currency();
+        let rate = self.fx_rate(&currency).await;
+        let results = usd
+            .into_iter()
+            .map(|(s, result)| match (result, &rate) {
+                (Ok(p), Ok(rate)) => (s, Ok(p.in_currency(&currency, *rate))),
+                (Ok(_), Err(e)) => (s, Err(e.clone())),
+                (Err(e), _) => (s, Err(e)),
+            })
+            .collect();
+        let batch = PriceBatch { results };
+        for (_, result) in batch.iter() {
+            match result {
+                Ok(p) => self.metrics.record_price(p, now),
+                Err(e) => self
+                    .metrics
+                    .record_failure(self.price_failure_extractor(e), e),
+            }
+        }
+        if let Some(store) = &self.store {
+            let prices: Vec<_> = batch.prices().collect();
+            if let Err(e) = store.insert_prices(&prices, &self.source(), now) {
+                eprintln!("Could not record the prices: {}", e);
             }
-            result
-        });
-        Ok(x)
+        }
+        batch
     }
     ///Parses the html snippet and extracts the price from it and creates a new PriceResult which is then returned.
     /// # Arguments
@@ -247,38 +721,71 @@ impl CoinMarketCapScrapper {
     /// # Errors
     /// If there is a parse error or chromedriver is not available but needed an error is returned
     fn parse_price(
-        html: String,
+        html: &str,
         symbol: String,
         reg_price_section: Arc<String>,
         per_regex: Arc<String>,
     ) -> Result<PriceResult, ParseError> {
         let price = match html::get_inner_html_from_element(
             &reg_price_section,
-            &html,
+            html,
             vec![vec![ElementRelation::Child(0)]],
         ) {
             Ok(s) => String::from(&s[0]),
             Err(e) => return Err(e),
         };
-        let price = price
-            .replace("$", "")
-            .replace(",", "")
-            .parse::<f64>()
-            .unwrap();
         //Percentage
         let percentage = match html::get_inner_html_from_element(
             &reg_price_section,
-            &html,
+            html,
             vec![vec![ElementRelation::Child(0), ElementRelation::Sibling(0)]],
         ) {
             Ok(s) => String::from(&s[0]),
             Err(e) => return Err(e),
         };
-        let re = Regex::new(&per_regex).unwrap();
+        CoinMarketCapScrapper::price_from_parts(symbol, price, percentage, &per_regex)
+    }
+    ///Parses the html snippet with the path expressions `price_path` and `change_path` (see
+    /// `html::get_inner_html_from_path`) and creates a new PriceResult which is then returned.
+    /// # Arguments
+    /// * `html` - The html snippet which contains the price string.
+    /// * `symbol` - The symbol for which the price is extracted.
+    /// * `price_path` - The path expression of the element containing the price
+    /// * `change_path` - The path expression of the element containing the percentage change
+    /// * `per_regex` - The regex expression to extract the percentage change from its element
+    fn parse_price_with_selectors(
+        html: &str,
+        symbol: String,
+        price_path: &str,
+        change_path: &str,
+        per_regex: &str,
+    ) -> Result<PriceResult, ParseError> {
+        let price = html::get_inner_html_from_path(price_path, html)?;
+        let percentage = html::get_inner_html_from_path(change_path, html)?;
+        CoinMarketCapScrapper::price_from_parts(symbol, price, percentage, per_regex)
+    }
+    ///Creates a new PriceResult from the scraped `price` text and the html snippet `percentage` containing the
+    /// percentage change which is matched with `per_regex`
+    fn price_from_parts(
+        symbol: String,
+        price: String,
+        percentage: String,
+        per_regex: &str,
+    ) -> Result<PriceResult, ParseError> {
+        let price = parse_number(&price.replace("$", "").replace(",", ""), &price)?;
+        let re = match Regex::new(per_regex) {
+            Ok(re) => re,
+            Err(e) => {
+                return Err(ParseError::InvalidPattern {
+                    pattern: String::from(per_regex),
+                    details: e.to_string(),
+                })
+            }
+        };
         let cap = re.captures(&percentage);
         let change = match cap {
             Some(c) => {
-                let p = String::from(&c[2]).parse::<f64>().unwrap();
+                let p = parse_number::<f64>(&c[2], &c[2])?;
                 let s = match String::from(&c[1]).find("icon-Caret-up") {
                     Some(_) => 1.0,
                     None => -1.0,
@@ -286,16 +793,77 @@ impl CoinMarketCapScrapper {
                 p * s
             }
             None => {
-                return Err(ParseError::new(format!(
-                    "Error when reading page. Check manually for the symbol {}",
-                    symbol
-                )));
+                return Err(ParseError::ElementNotFound {
+                    pattern: String::from(per_regex),
+                });
             }
         };
         Ok(PriceResult {
             symbol,
             price,
             change,
+            currency: String::from(fx::USD),
+            ..Default::default()
+        })
+    }
+    ///Adds the statistics shown on the currency page `html` to `price`. Every statistic is matched with its
+    /// pattern in `patterns`; statistics which are not found are left out. The 1h and 7d changes are taken from
+    /// the quote of the coin in `__NEXT_DATA__` if they have no pattern.
+    /// # Errors
+    /// If one of the patterns is not a valid regular expression an error is returned
+    fn parse_statistics(
+        price: PriceResult,
+        html: &str,
+        patterns: &StatisticsPatterns,
+    ) -> Result<PriceResult, ParseError> {
+        let capture = |pattern: &str| -> Result<Option<String>, ParseError> {
+            if pattern.is_empty() {
+                return Ok(None);
+            }
+            let re = match Regex::new(pattern) {
+                Ok(re) => re,
+                Err(e) => {
+                    return Err(ParseError::InvalidPattern {
+                        pattern: String::from(pattern),
+                        details: e.to_string(),
+                    })
+                }
+            };
+            //values like "$930,332,625,053" or "18,644,618 BTC"
+            Ok(re.captures(html).and_then(|c| c.get(1)).map(|m| {
+                let text = m.as_str().trim().replace("$", "").replace(",", "");
+                String::from(text.split_whitespace().next().unwrap_or(""))
+            }))
+        };
+        let decimal = |pattern: &str| -> Result<Option<Decimal>, ParseError> {
+            Ok(capture(pattern)?.and_then(|t| t.parse().ok()))
+        };
+        let percent = |pattern: &str| -> Result<Option<f64>, ParseError> {
+            Ok(capture(pattern)?.and_then(|t| t.trim_end_matches('%').parse().ok()))
+        };
+        let quote = if patterns.change_1h.is_empty() || patterns.change_7d.is_empty() {
+            next_data::parse_changes(html)
+        } else {
+            (None, None)
+        };
+        let change = |pattern: &str, quote: Option<f64>| -> Result<Option<f64>, ParseError> {
+            if pattern.is_empty() {
+                Ok(quote)
+            } else {
+                percent(pattern)
+            }
+        };
+        Ok(PriceResult {
+            market_cap: decimal(&patterns.market_cap)?,
+            fully_diluted_market_cap: decimal(&patterns.fully_diluted_market_cap)?,
+            volume_24h: decimal(&patterns.volume_24h)?,
+            rank: capture(&patterns.rank)?.and_then(|t| t.parse().ok()),
+            circulating_supply: decimal(&patterns.circulating_supply)?,
+            total_supply: decimal(&patterns.total_supply)?,
+            max_supply: decimal(&patterns.max_supply)?,
+            change_1h: change(&patterns.change_1h, quote.0)?,
+            change_7d: change(&patterns.change_7d, quote.1)?,
+            ..price
         })
     }
     ///Returns the price as a `PriceResult` of the symbols stored in the `symbol` passed to the function
@@ -304,8 +872,8 @@ impl CoinMarketCapScrapper {
     /// # Errors
     /// If there is a parse error or chromedriver is not available but needed an error is returned
     pub fn get_price(&mut self, symbol: &str) -> Result<PriceResult, ParseError> {
-        let mut result = self.get_prices(&vec![String::from(symbol)]).unwrap();
-        Ok(result.pop().unwrap())
+        let batch = self.get_prices(&vec![String::from(symbol)]);
+        batch.into_iter().next().unwrap().1
     }
 
     ///Returns Market data as a `MarketResult` of the symbol stored in the `symbol` passed to the function
@@ -318,14 +886,72 @@ impl CoinMarketCapScrapper {
         &mut self,
         symbol: &str,
         number_of_results: i32,
+    ) -> Result<Vec<MarketResult>, ParseError> {
+        let markets = match self.scrape_market_data(symbol, number_of_results) {
+            Ok(m) => m,
+            Err(e) => {
+                self.metrics.record_failure("market_table", &e);
+                return Err(e);
+            }
+        };
+        let currency = self.currency();
+        let rate = self.runtime.block_on(self.fx_rate(&currency))?;
+        let markets: Vec<MarketResult> = markets
+            .into_iter()
+            .map(|m| m.in_currency(&currency, rate))
+            .collect();
+        self.metrics
+            .record_success("markets", symbol, SystemTime::now());
+        if let Some(store) = &self.store {
+            if let Err(e) =
+                store.insert_markets(symbol, &markets, &self.source(), SystemTime::now())
+            {
+                eprintln!("Could not record the markets of {}: {}", symbol, e);
+            }
+        }
+        Ok(markets)
+    }
+    fn scrape_market_data(
+        &mut self,
+        symbol: &str,
+        number_of_results: i32,
     ) -> Result<Vec<MarketResult>, ParseError> {
         let mut result = Vec::new();
         let url = format!("https://coinmarketcap.com/currencies/{}/markets", symbol);
         let c = Arc::clone(&self.html_parser.cache);
-        let html = match self.runtime.block_on(html::get_html(c, &url, false, true)) {
+        let f = Arc::clone(&self.html_parser.fetcher);
+        let policy = CachePolicy::from_config(
+            self.cfg.configuration.market_refresh_after,
+            CachePolicy::Forever,
+        );
+        let html = match self
+            .runtime
+            .block_on(html::get_html(c, f, &url, policy, true))
+        {
             Ok(html) => html,
             Err(err) => return Err(err),
         };
+        match self.cfg.configuration.extractor {
+            Extractor::NextData => return next_data::parse_markets(&html, number_of_results),
+            Extractor::Selectors => {
+                let selectors = &self.cfg.configuration.selectors;
+                for i in 0..number_of_results {
+                    let row = format!("({})", i + 1);
+                    let get = |path: &String| {
+                        html::get_inner_html_from_path(&path.replace("(n)", &row), &html)
+                    };
+                    result.push(self.market_from_parts(
+                        get(&selectors.market_source)?,
+                        get(&selectors.market_pair)?,
+                        get(&selectors.market_price)?,
+                        get(&selectors.market_volume)?,
+                        get(&selectors.market_volume_percent)?,
+                    )?);
+                }
+                return Ok(result);
+            }
+            Extractor::Markup => {}
+        }
         for i in 0..number_of_results {
             let regex = r#"<(table) (class=".*?currencies-markets_.*? ")>"#;
             let rel_source = vec![vec![
@@ -346,38 +972,341 @@ impl CoinMarketCapScrapper {
             let inner_price = html::get_inner_html_from_element(regex, &html, rel_price)?;
             let inner_vol = html::get_inner_html_from_element(regex, &html, rel_vol)?;
             let inner_vol_perc = html::get_inner_html_from_element(regex, &html, rel_vol_perc)?;
-            result.push(MarketResult {
-                source: String::from(&inner_source[0]),
-                pair: String::from(&inner_pairs[0]),
-                price: self.cleanup_number(&inner_price[0]).parse::<f64>().unwrap(),
-                volume: self.cleanup_number(&inner_vol[0]).parse::<f64>().unwrap(),
-                volume_percent: self
-                    .cleanup_number(&inner_vol_perc[0].replace(",", "."))
-                    .parse::<f64>()
-                    .unwrap(),
-            });
+            result.push(self.market_from_parts(
+                String::from(&inner_source[0]),
+                String::from(&inner_pairs[0]),
+                String::from(&inner_price[0]),
+                String::from(&inner_vol[0]),
+                String::from(&inner_vol_perc[0]),
+            )?);
         } //for
         return Ok(result);
     } //fn get_market_data
+
+    ///Creates a new `MarketResult` from the scraped texts of the market table
+    fn market_from_parts(
+        &self,
+        source: String,
+        pair: String,
+        price: String,
+        volume: String,
+        volume_percent: String,
+    ) -> Result<MarketResult, ParseError> {
+        Ok(MarketResult {
+            source,
+            pair,
+            price: parse_number(&self.cleanup_number(&price), &price)?,
+            volume: parse_number(&self.cleanup_number(&volume), &volume)?,
+            volume_percent: parse_number(
+                &self.cleanup_number(&volume_percent.replace(",", ".")),
+                &volume_percent,
+            )?,
+            currency: String::from(fx::USD),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::CoinMarketCapScrapper;
+    use super::{fx, next_data, CoinMarketCapScrapper, Decimal, PriceResult, Store};
+    use crate::alert::sinks::LogFileSink;
+    use crate::alert::AlertEngine;
+    use crate::config::{AlertCondition, AlertRule, Selectors, StatisticsPatterns};
+    use crate::html::{FileFetcher, PageFetcher, ParseError, RecordingFetcher};
+    use std::sync::{Arc, Mutex};
+
+    ///Returns the path of a copy of the fixture config for the test `case`. The config is stored again when it
+    /// is dropped, so tests never open the config of the user.
+    fn fixture_config(case: &str) -> String {
+        let config = std::env::temp_dir().join(format!("crypto_scrapper_{}.toml", case));
+        std::fs::copy("./tests/fixtures/config.toml", &config).unwrap();
+        String::from(config.to_str().unwrap())
+    }
     #[test]
+    #[ignore = "needs the live site and a running chromedriver"]
     fn test_get_price_existing_symbol() {
-        let scrapper = CoinMarketCapScrapper::new(String::from("./config/config.toml")).unwrap();
+        let mut scrapper = CoinMarketCapScrapper::new(fixture_config("existing_symbol")).unwrap();
         let result = scrapper.get_price("multi-collateral-dai").unwrap();
-        assert_eq!(result.price, 1.0); //testing with stable coin. expected value is 1.0
+        assert_eq!(result.price, Decimal::ONE); //testing with stable coin. expected value is 1.0
         assert!(result.change < 0.2 && result.change > -0.2); //testing with a stable coin. Difference should be less than 0.2%
     }
     #[test]
+    #[ignore = "needs the live site and a running chromedriver"]
     fn test_get_price_non_existing_symbol() {
-        let scrapper = CoinMarketCapScrapper::new(String::from("./config/config.toml")).unwrap();
-        let result = scrapper.get_price("aseff");
+        let mut scrapper =
+            CoinMarketCapScrapper::new(fixture_config("non_existing_symbol")).unwrap();
+        assert!(scrapper.get_price("aseff").is_err());
+    }
+    #[test]
+    fn test_get_price_from_saved_page() {
+        let fetcher = FileFetcher::new("./").with_page(
+            "https://coinmarketcap.com/currencies/bitcoin/",
+            "bitcoin.html",
+        );
+        let mut scrapper =
+            CoinMarketCapScrapper::with_fetcher(fixture_config("saved_page"), Arc::new(fetcher))
+                .unwrap();
+        let result = scrapper.get_price("bitcoin").unwrap();
+        assert_eq!(result.price, "49898.19".parse().unwrap());
+        assert_eq!(result.change, -1.48);
+    }
+    #[test]
+    fn test_record_and_replay() {
+        let directory = std::env::temp_dir().join("crypto_scrapper_record_and_replay");
+        let directory = directory.to_str().unwrap();
+        let fetcher = FileFetcher::new("./").with_page(
+            "https://coinmarketcap.com/currencies/bitcoin/",
+            "bitcoin.html",
+        );
+        let fetcher = RecordingFetcher::new(Arc::new(fetcher), directory);
+        let mut scrapper =
+            CoinMarketCapScrapper::with_fetcher(fixture_config("recording"), Arc::new(fetcher))
+                .unwrap();
+        let recorded = scrapper.get_price("bitcoin").unwrap();
+        let mut scrapper =
+            CoinMarketCapScrapper::replaying(fixture_config("replaying"), directory).unwrap();
+        let replayed = scrapper.get_price("bitcoin").unwrap();
+        assert_eq!(recorded.price, replayed.price);
+        assert_eq!(recorded.change, replayed.change);
+        assert!(scrapper.get_details("bitcoin").is_err()); //details were never recorded
+    }
+    #[test]
+    fn test_layout_change_errors() {
+        let html = std::fs::read_to_string("./bitcoin.html").unwrap();
+        let missing = String::from("(div) (class=\"priceMissing__.*?\")>");
+        let result = CoinMarketCapScrapper::parse_price(
+            &html,
+            String::from("bitcoin"),
+            Arc::new(String::from(&missing)),
+            Arc::new(String::from("")),
+        );
         match result {
-            Ok(_) => assert!(false, "expected an error"),
-            Err(e) => assert!(true, format!("Error is {}", e)),
+            Err(ParseError::ElementNotFound { pattern }) => assert_eq!(pattern, missing),
+            _ => panic!("expected ElementNotFound"),
+        }
+        let error = ParseError::NumberParse {
+            text: String::from("$--"),
         };
+        assert!(error.is_layout_change() && !error.is_transient());
+    }
+    #[test]
+    fn test_get_prices_partial_success() {
+        let fetcher = FileFetcher::new("./").with_page(
+            "https://coinmarketcap.com/currencies/bitcoin/",
+            "bitcoin.html",
+        );
+        let scrapper = CoinMarketCapScrapper::with_fetcher(
+            fixture_config("partial_success"),
+            Arc::new(fetcher),
+        )
+        .unwrap();
+        let symbols = vec![
+            String::from("aseff"),
+            String::from("bitcoin"),
+            String::from("aseff"),
+        ];
+        let batch = scrapper.get_prices(&symbols);
+        assert_eq!(batch.len(), 3);
+        assert!(!batch.is_complete());
+        let order: Vec<(&str, bool)> = batch.iter().map(|(s, r)| (s.as_str(), r.is_ok())).collect();
+        assert_eq!(
+            order,
+            vec![("aseff", false), ("bitcoin", true), ("aseff", false)]
+        );
+        assert_eq!(
+            batch.prices().next().unwrap().price,
+            "49898.19".parse().unwrap()
+        );
+        assert_eq!(batch.errors().count(), 2);
+    }
+    ///Panics for every page but the one of bitcoin
+    struct PanickingFetcher(FileFetcher);
+    #[async_trait::async_trait]
+    impl PageFetcher for PanickingFetcher {
+        async fn fetch(&self, url: &str, script: bool) -> Result<String, ParseError> {
+            if !url.contains("bitcoin") {
+                panic!("fetcher panicked for {}", url);
+            }
+            self.0.fetch(url, script).await
+        }
+    }
+    #[test]
+    fn test_get_prices_task_panic() {
+        let fetcher = FileFetcher::new("./").with_page(
+            "https://coinmarketcap.com/currencies/bitcoin/",
+            "bitcoin.html",
+        );
+        let scrapper = CoinMarketCapScrapper::with_fetcher(
+            fixture_config("task_panic"),
+            Arc::new(PanickingFetcher(fetcher)),
+        )
+        .unwrap();
+        let symbols = vec![String::from("aseff"), String::from("bitcoin")];
+        let batch = scrapper.get_prices(&symbols);
+        assert_eq!(batch.prices().count(), 1);
+        let errors: Vec<_> = batch.errors().collect();
+        match errors.as_slice() {
+            [("aseff", ParseError::Task { .. })] => {}
+            e => panic!("expected a task error, got {:?}", e),
+        }
+    }
+    #[test]
+    fn test_store_records_prices() {
+        let fetcher = FileFetcher::new("./").with_page(
+            "https://coinmarketcap.com/currencies/bitcoin/",
+            "bitcoin.html",
+        );
+        let mut scrapper =
+            CoinMarketCapScrapper::with_fetcher(fixture_config("store"), Arc::new(fetcher))
+                .unwrap();
+        assert!(scrapper.latest_prices("bitcoin", 1).is_err());
+        scrapper.store = Some(Store::open_in_memory().unwrap());
+        scrapper.get_price("bitcoin").unwrap();
+        let latest = scrapper.latest_prices("bitcoin", 5).unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].price.price, "49898.19".parse().unwrap());
+        assert_eq!(latest[0].source, "coinmarketcap.com/markup");
+        let now = std::time::SystemTime::now();
+        assert_eq!(
+            scrapper.price_at("bitcoin", now).unwrap(),
+            Some(latest[0].clone())
+        );
+    }
+    #[test]
+    fn test_alerts_after_fetch() {
+        let fetcher = FileFetcher::new("./")
+            .with_page(
+                "https://coinmarketcap.com/currencies/bitcoin/",
+                "bitcoin.html",
+            )
+            .with_page(fx::ECB_RATES_URL, "./tests/fixtures/eurofxref-daily.xml");
+        let mut scrapper =
+            CoinMarketCapScrapper::with_fetcher(fixture_config("fetch_alerts"), Arc::new(fetcher))
+                .unwrap();
+        let log = std::env::temp_dir().join("crypto_scrapper_fetch_alerts.log");
+        let _ = std::fs::remove_file(&log);
+        let rule = |condition| AlertRule {
+            name: None,
+            symbol: String::from("bitcoin"),
+            condition,
+            hysteresis: None,
+            cooldown: None,
+        };
+        let mut engine = AlertEngine::new(vec![
+            rule(AlertCondition::Above { price: 40000.0 }),
+            rule(AlertCondition::Below { price: 46000.0 }),
+        ]);
+        engine.add_sink(Box::new(LogFileSink::new(log.to_str().unwrap())));
+        scrapper.alerts = Mutex::new(engine);
+        scrapper.get_price("bitcoin").unwrap();
+        scrapper.get_price("bitcoin").unwrap();
+        //the price in CHF is below 46000, but the rules are checked on the USD price
+        scrapper.cfg.configuration.currency = Some(String::from("chf"));
+        let price = scrapper.get_price("bitcoin").unwrap();
+        assert!(price.price().to_f64() < 46000.0);
+        let lines = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(lines.lines().count(), 1);
+        assert!(lines.ends_with("[bitcoin above 40000] bitcoin is above 40000 at 49898.19\n"));
+    }
+    #[test]
+    fn test_next_data_extractor() {
+        let html = std::fs::read_to_string("./bitcoin.html").unwrap();
+        let price = next_data::parse_price(&html, String::from("bitcoin")).unwrap();
+        assert_eq!(price.price, "49898.18643924657".parse().unwrap());
+        assert_eq!(price.change, -1.47684929);
+        assert_eq!(price.market_cap, Some("930332625052.53".parse().unwrap()));
+        assert_eq!(price.volume_24h, Some("50103411503.91895".parse().unwrap()));
+        assert_eq!(price.rank, Some(1));
+        assert_eq!(price.max_supply, Some("21000000".parse().unwrap()));
+        assert_eq!(price.change_1h, Some(0.88219637));
+        assert_eq!(price.change_7d, Some(-1.71749491));
+        let markets = next_data::parse_markets(&html, 3).unwrap();
+        assert_eq!(markets.len(), 3);
+        assert_eq!(markets[0].source, "Coinbase Pro");
+        assert_eq!(markets[0].pair, "BTC/USD");
+        assert_eq!(markets[0].price, "49711.9".parse().unwrap());
+        let details = next_data::parse_details(&html).unwrap();
+        assert!(details.starts_with("\n------------What Is Bitcoin (BTC)?------------\n"));
+        assert!(!details.contains("](https://"));
+        //more digits than a f64 holds
+        let html = r#"<script id="__NEXT_DATA__" type="application/json">{"props":{"initialProps":{"pageProps":
+            {"info":{"statistics":{"price":12345678901234567.123456789,"priceChangePercentage24h":1.5}}}}}}</script>"#;
+        let price = next_data::parse_price(html, String::from("bitcoin")).unwrap();
+        assert_eq!(price.price.to_string(), "12345678901234567.123456789");
+    }
+    #[test]
+    fn test_volume_in_dollars() {
+        let html = std::fs::read_to_string("./bitcoin.html").unwrap();
+        let markets = next_data::parse_markets(&html, 3).unwrap();
+        let upbit = markets.iter().find(|m| m.source == "Upbit").unwrap();
+        assert_eq!(upbit.price, "50671.73671130186".parse().unwrap());
+        assert_eq!(upbit.volume, "541896437.7516882".parse().unwrap());
+        assert_eq!(
+            upbit.get_volume_in_dollars(),
+            Some("10694.254290889998944914".parse().unwrap())
+        );
+        //the rate cancels out, so the markets in CHF have the same volume in USD up to the last place
+        let xml = std::fs::read_to_string("./tests/fixtures/eurofxref-daily.xml").unwrap();
+        let rate = fx::usd_rate(&fx::parse_ecb_rates(&xml).unwrap(), "CHF").unwrap();
+        let volumes: Vec<Decimal> = markets
+            .into_iter()
+            .map(|m| m.in_currency("CHF", rate).get_volume_in_dollars().unwrap())
+            .collect();
+        let expected: Vec<Decimal> = [
+            "19752.5",
+            "16882.040168999998168046",
+            "10694.254290889998944914",
+        ]
+        .iter()
+        .map(|v| v.parse().unwrap())
+        .collect();
+        assert_eq!(volumes, expected);
+    }
+
+    #[test]
+    fn test_selectors_extractor() {
+        let html = std::fs::read_to_string("./bitcoin.html").unwrap();
+        let selectors = Selectors::default();
+        let result = CoinMarketCapScrapper::parse_price_with_selectors(
+            &html,
+            String::from("bitcoin"),
+            &selectors.price,
+            &selectors.price_change,
+            r#"<span class="(.{1,20})"></span>([0-9]+[.]?[0-9]*)<!-- -->%"#,
+        )
+        .unwrap();
+        assert_eq!(result.price, "49898.19".parse().unwrap());
+        assert_eq!(result.change, -1.48);
+    }
+    #[test]
+    fn test_statistics() {
+        let html = std::fs::read_to_string("./bitcoin.html").unwrap();
+        let d = |text: &str| Some(text.parse().unwrap());
+        let mut patterns = StatisticsPatterns::default();
+        let price =
+            CoinMarketCapScrapper::parse_statistics(PriceResult::default(), &html, &patterns)
+                .unwrap();
+        assert_eq!(price.market_cap, d("930332625053"));
+        assert_eq!(price.fully_diluted_market_cap, d("1047861915224"));
+        assert_eq!(price.volume_24h, d("50103411504"));
+        assert_eq!(price.rank, Some(1));
+        assert_eq!(price.circulating_supply, d("18644618"));
+        assert_eq!(price.total_supply, d("18644618"));
+        assert_eq!(price.max_supply, d("21000000"));
+        //without a pattern the changes are taken from the quote of the coin
+        assert_eq!(price.change_1h, Some(0.88219637));
+        assert_eq!(price.change_7d, Some(-1.71749491));
+        //a configured pattern is used instead of the quote
+        patterns.change_7d = String::from("change7dMissing(.*)");
+        patterns.max_supply = String::from("maxSupplyMissing(.*)");
+        let price = CoinMarketCapScrapper::parse_statistics(price, &html, &patterns).unwrap();
+        assert_eq!(price.change_1h, Some(0.88219637));
+        assert_eq!(price.change_7d, None);
+        assert_eq!(price.max_supply, None);
+        patterns.rank = String::from("(");
+        match CoinMarketCapScrapper::parse_statistics(price, &html, &patterns) {
+            Err(ParseError::InvalidPattern { pattern, .. }) => assert_eq!(pattern, "("),
+            r => panic!("expected an invalid pattern, got {:?}", r),
+        }
     }
 }
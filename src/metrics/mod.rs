@@ -0,0 +1,338 @@
+use crate::html::{CacheStatistics, PageFetcher, ParseError};
+use crate::PriceResult;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+///Upper bounds in seconds of the buckets of the fetch latency histograms
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+///A Prometheus histogram with the `LATENCY_BUCKETS`
+#[derive(Default)]
+struct Histogram {
+    ///Number of observations per bucket, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|b| seconds <= *b) {
+            self.buckets[i] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct MetricsData {
+    ///Last price of every symbol
+    prices: BTreeMap<String, PriceResult>,
+    ///Fetch latencies by path, "static" or "webdriver"
+    latency: BTreeMap<&'static str, Histogram>,
+    ///Parse failures by extractor, e.g. "price" or "market_table"
+    parse_failures: BTreeMap<&'static str, u64>,
+    ///Last successful scrape by operation ("price", "markets" or "details") and symbol
+    last_success: BTreeMap<(&'static str, String), SystemTime>,
+}
+
+///Collects the metrics of a `CoinMarketCapScrapper` and renders them in the Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    data: Mutex<MetricsData>,
+}
+
+///Returns `text` escaped as a Prometheus label value
+fn label(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+///Writes the HELP and TYPE lines of the metric family `name` to `text`
+fn family(text: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(text, "# HELP {} {}", name, help).unwrap();
+    writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+}
+
+///Returns `time` as seconds since the unix epoch
+fn unix_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+impl Metrics {
+    ///Records that a page was fetched in `elapsed`, with the WebDriver if `script` is true
+    pub fn observe_fetch(&self, script: bool, elapsed: Duration) {
+        let path = if script { "webdriver" } else { "static" };
+        let mut data = self.data.lock().unwrap();
+        data.latency
+            .entry(path)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+    ///Records the scraped `price` as the current price of its symbol
+    pub fn record_price(&self, price: &PriceResult, now: SystemTime) {
+        let mut data = self.data.lock().unwrap();
+        data.prices.insert(price.symbol.clone(), price.clone());
+        data.last_success
+            .insert(("price", price.symbol.clone()), now);
+    }
+    ///Records that `operation` ("price", "markets" or "details") succeeded for `symbol`
+    pub fn record_success(&self, operation: &'static str, symbol: &str, now: SystemTime) {
+        self.data
+            .lock()
+            .unwrap()
+            .last_success
+            .insert((operation, String::from(symbol)), now);
+    }
+    ///Records `error` of `extractor` ("price", "percentage", "market_table" or "details") if it is a parse
+    /// failure, i.e. not an error loading the page or converting the price
+    pub fn record_failure(&self, extractor: &'static str, error: &ParseError) {
+        if let ParseError::Transport { .. }
+        | ParseError::WebDriver { .. }
+        | ParseError::Io { .. }
+        | ParseError::UnknownCurrency { .. } = error
+        {
+            return;
+        }
+        *self
+            .data
+            .lock()
+            .unwrap()
+            .parse_failures
+            .entry(extractor)
+            .or_default() += 1;
+    }
+
+    ///Returns all metrics and the statistics of the page cache in the Prometheus text format
+    pub fn render(&self, cache: &CacheStatistics) -> String {
+        let data = self.data.lock().unwrap();
+        let mut text = String::new();
+
+        family(
+            &mut text,
+            "crypto_scrapper_price",
+            "gauge",
+            "Last scraped price in the quote currency",
+        );
+        for (symbol, price) in &data.prices {
+            writeln!(
+                text,
+                "crypto_scrapper_price{{symbol=\"{}\",currency=\"{}\"}} {}",
+                label(symbol),
+                label(&price.currency),
+                price.price
+            )
+            .unwrap();
+        }
+        family(
+            &mut text,
+            "crypto_scrapper_change_24h_percent",
+            "gauge",
+            "Last scraped change of the price within 24 hours",
+        );
+        for (symbol, price) in &data.prices {
+            writeln!(
+                text,
+                "crypto_scrapper_change_24h_percent{{symbol=\"{}\"}} {}",
+                label(symbol),
+                price.change
+            )
+            .unwrap();
+        }
+
+        family(
+            &mut text,
+            "crypto_scrapper_fetch_duration_seconds",
+            "histogram",
+            "Time to load a page which was not cached",
+        );
+        for (path, histogram) in &data.latency {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets.iter()) {
+                cumulative += count;
+                writeln!(
+                    text,
+                    "crypto_scrapper_fetch_duration_seconds_bucket{{path=\"{}\",le=\"{}\"}} {}",
+                    path, bound, cumulative
+                )
+                .unwrap();
+            }
+            writeln!(
+                text,
+                "crypto_scrapper_fetch_duration_seconds_bucket{{path=\"{}\",le=\"+Inf\"}} {}",
+                path, histogram.count
+            )
+            .unwrap();
+            writeln!(
+                text,
+                "crypto_scrapper_fetch_duration_seconds_sum{{path=\"{}\"}} {}",
+                path, histogram.sum
+            )
+            .unwrap();
+            writeln!(
+                text,
+                "crypto_scrapper_fetch_duration_seconds_count{{path=\"{}\"}} {}",
+                path, histogram.count
+            )
+            .unwrap();
+        }
+
+        family(
+            &mut text,
+            "crypto_scrapper_cache_lookups_total",
+            "counter",
+            "Lookups of the page cache by result",
+        );
+        for (result, count) in [
+            ("hit", cache.hits),
+            ("miss", cache.misses),
+            ("stale", cache.stale),
+        ]
+        .iter()
+        {
+            writeln!(
+                text,
+                "crypto_scrapper_cache_lookups_total{{result=\"{}\"}} {}",
+                result, count
+            )
+            .unwrap();
+        }
+        family(
+            &mut text,
+            "crypto_scrapper_cache_evictions_total",
+            "counter",
+            "Pages evicted from the cache because a limit was exceeded",
+        );
+        writeln!(
+            text,
+            "crypto_scrapper_cache_evictions_total {}",
+            cache.evictions
+        )
+        .unwrap();
+        family(
+            &mut text,
+            "crypto_scrapper_cache_entries",
+            "gauge",
+            "Pages held in the cache",
+        );
+        writeln!(text, "crypto_scrapper_cache_entries {}", cache.entries).unwrap();
+
+        family(
+            &mut text,
+            "crypto_scrapper_parse_failures_total",
+            "counter",
+            "Pages which did not match the configured extractor",
+        );
+        for (extractor, count) in &data.parse_failures {
+            writeln!(
+                text,
+                "crypto_scrapper_parse_failures_total{{extractor=\"{}\"}} {}",
+                extractor, count
+            )
+            .unwrap();
+        }
+
+        family(
+            &mut text,
+            "crypto_scrapper_last_success_timestamp_seconds",
+            "gauge",
+            "Unix time of the last successful scrape",
+        );
+        for ((operation, symbol), time) in &data.last_success {
+            writeln!(
+                text,
+                "crypto_scrapper_last_success_timestamp_seconds{{operation=\"{}\",symbol=\"{}\"}} {}",
+                operation,
+                label(symbol),
+                unix_seconds(*time)
+            )
+            .unwrap();
+        }
+        text
+    }
+}
+
+///A `PageFetcher` which records the time another fetcher takes to load a page in `Metrics`
+pub struct TimedFetcher {
+    inner: Arc<dyn PageFetcher>,
+    metrics: Arc<Metrics>,
+}
+impl TimedFetcher {
+    ///Returns a new `TimedFetcher` loading the pages with `inner`
+    pub fn new(inner: Arc<dyn PageFetcher>, metrics: Arc<Metrics>) -> TimedFetcher {
+        TimedFetcher { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl PageFetcher for TimedFetcher {
+    async fn fetch(&self, url: &str, script: bool) -> Result<String, ParseError> {
+        let start = Instant::now();
+        let source = self.inner.fetch(url, script).await;
+        self.metrics.observe_fetch(script, start.elapsed());
+        source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metrics;
+    use crate::html::{CacheStatistics, ParseError};
+    use crate::PriceResult;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        let price = PriceResult {
+            symbol: String::from("bitcoin"),
+            price: "49898.19".parse().unwrap(),
+            change: -1.48,
+            currency: String::from("CHF"),
+            ..Default::default()
+        };
+        metrics.record_price(&price, UNIX_EPOCH + Duration::from_secs(1_614_000_000));
+        metrics.observe_fetch(false, Duration::from_millis(200));
+        metrics.observe_fetch(false, Duration::from_secs(3));
+        metrics.record_failure(
+            "percentage",
+            &ParseError::ElementNotFound {
+                pattern: String::from("<span"),
+            },
+        );
+        metrics.record_failure(
+            "price",
+            &ParseError::WebDriver {
+                url: String::from("https://coinmarketcap.com/"),
+                details: String::new(),
+            },
+        );
+        let cache = CacheStatistics {
+            hits: 4,
+            misses: 2,
+            ..Default::default()
+        };
+        let text = metrics.render(&cache);
+        for line in [
+            "crypto_scrapper_price{symbol=\"bitcoin\",currency=\"CHF\"} 49898.19",
+            "crypto_scrapper_change_24h_percent{symbol=\"bitcoin\"} -1.48",
+            "crypto_scrapper_fetch_duration_seconds_bucket{path=\"static\",le=\"0.25\"} 1",
+            "crypto_scrapper_fetch_duration_seconds_bucket{path=\"static\",le=\"5\"} 2",
+            "crypto_scrapper_fetch_duration_seconds_count{path=\"static\"} 2",
+            "crypto_scrapper_cache_lookups_total{result=\"hit\"} 4",
+            "crypto_scrapper_parse_failures_total{extractor=\"percentage\"} 1",
+            "crypto_scrapper_last_success_timestamp_seconds{operation=\"price\",symbol=\"bitcoin\"} 1614000000",
+        ]
+        .iter()
+        {
+            assert!(text.lines().any(|l| l == *line), "missing {}", line);
+        }
+        assert!(!text.contains("extractor=\"price\""));
+    }
+}
@@ -0,0 +1,482 @@
+pub use crate::config::RoundingMode;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+///Maximum number of decimal places kept. Longer fractions are rounded half to even.
+pub const MAX_SCALE: u32 = 18;
+
+///The text could not be parsed as a `Decimal`
+#[derive(Debug, PartialEq)]
+pub struct ParseDecimalError {
+    pub text: String,
+}
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\" is not a decimal number", self.text)
+    }
+}
+
+impl Error for ParseDecimalError {}
+
+///A fixed-point decimal number, `mantissa * 10^-scale`. Prices and volumes are kept in this type from the
+/// scraped text to the output so that no digit is changed by a binary floating point conversion.
+///
+/// The scale is kept as parsed, e.g. "1.50" is displayed with two decimal places, but compares equal to "1.5".
+/// Serialized as a string to keep all digits in JSON.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal {
+        mantissa: 0,
+        scale: 0,
+    };
+    pub const ONE: Decimal = Decimal {
+        mantissa: 1,
+        scale: 0,
+    };
+
+    ///Returns `mantissa * 10^-scale`, rounded half to even if `scale` is greater than `MAX_SCALE`
+    pub fn new(mantissa: i128, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }.round(MAX_SCALE, RoundingMode::HalfEven)
+    }
+    ///Returns the decimal with the shortest representation which converts back to `value`, e.g. 0.1 for 0.1.
+    /// Returns None for NaN and infinite values.
+    pub fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None;
+        }
+        //the Display of f64 is the shortest text which round-trips and never uses an exponent
+        value.to_string().parse().ok()
+    }
+    ///Returns the nearest f64, e.g. for charts and comparisons with configured thresholds
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+    ///Returns the number of decimal places
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+    pub fn is_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    ///Returns the number rounded to `places` decimal places with `mode`. Numbers with fewer places are returned
+    /// unchanged, they are not padded with zeros.
+    pub fn round(&self, places: u32, mode: RoundingMode) -> Decimal {
+        if places >= self.scale {
+            return *self;
+        }
+        let negative = self.is_negative();
+        let mut quotient = self.mantissa.unsigned_abs();
+        //the most significant dropped digit and whether any digit after it is not zero
+        let mut first_dropped = 0;
+        let mut rest = false;
+        for _ in places..self.scale {
+            rest |= first_dropped != 0;
+            first_dropped = quotient % 10;
+            quotient /= 10;
+        }
+        let inexact = first_dropped != 0 || rest;
+        let up = match mode {
+            RoundingMode::HalfEven => {
+                first_dropped > 5 || (first_dropped == 5 && (rest || quotient % 2 == 1))
+            }
+            RoundingMode::HalfUp => first_dropped >= 5,
+            RoundingMode::HalfDown => first_dropped > 5 || (first_dropped == 5 && rest),
+            RoundingMode::Down => false,
+            RoundingMode::Up => inexact,
+            RoundingMode::Floor => negative && inexact,
+            RoundingMode::Ceiling => !negative && inexact,
+        };
+        if up {
+            quotient += 1;
+        }
+        let quotient = quotient as i128;
+        Decimal {
+            mantissa: if negative { -quotient } else { quotient },
+            scale: places,
+        }
+    }
+
+    ///Returns the mantissa of the number with `scale` decimal places, or None if it does not fit
+    fn mantissa_at(&self, scale: u32) -> Option<i128> {
+        if scale < self.scale {
+            return None;
+        }
+        10i128
+            .checked_pow(scale - self.scale)?
+            .checked_mul(self.mantissa)
+    }
+    ///Returns the number without trailing zeros after the decimal point
+    fn normalize(&self) -> Decimal {
+        let mut result = *self;
+        while result.scale > 0 && result.mantissa % 10 == 0 {
+            result.mantissa /= 10;
+            result.scale -= 1;
+        }
+        result
+    }
+
+    ///Returns the sum of both numbers, or None if it does not fit
+    pub fn checked_add(&self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let mantissa = self
+            .mantissa_at(scale)?
+            .checked_add(other.mantissa_at(scale)?)?;
+        Some(Decimal { mantissa, scale })
+    }
+
+    ///Returns the product of both numbers rounded to `MAX_SCALE` decimal places. If the exact product does not
+    /// fit, the operand with more decimal places is rounded first. Returns None if the integer part overflows.
+    pub fn checked_mul(&self, other: Decimal) -> Option<Decimal> {
+        let (mut a, mut b) = (self.normalize(), other.normalize());
+        loop {
+            if let Some(mantissa) = a.mantissa.checked_mul(b.mantissa) {
+                return Some(Decimal::new(mantissa, a.scale + b.scale));
+            }
+            if a.scale == 0 && b.scale == 0 {
+                return None;
+            }
+            if a.scale >= b.scale {
+                a = a.round(a.scale - 1, RoundingMode::HalfEven);
+            } else {
+                b = b.round(b.scale - 1, RoundingMode::HalfEven);
+            }
+        }
+    }
+
+    ///Returns the quotient of both numbers rounded half to even to `scale` decimal places. The quotient is
+    /// computed by long division, so the operands are never scaled beyond their own digits. Returns None if
+    /// `other` is zero or the quotient overflows.
+    pub fn checked_div(&self, other: Decimal, scale: u32) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let scale = scale.min(MAX_SCALE);
+        let (a, b) = (self.normalize(), other.normalize());
+        let divisor = b.mantissa.unsigned_abs();
+        //a / b = (|a.m| / |b.m|) / 10^(a.s - b.s), so the digits of |a.m| / |b.m| have to reach one place after
+        // `scale` for rounding
+        let digits = (scale + 1 + b.scale).saturating_sub(a.scale);
+        let mut quotient = a.mantissa.unsigned_abs() / divisor;
+        let mut remainder = a.mantissa.unsigned_abs() % divisor;
+        for _ in 0..digits {
+            let (digit, next) = next_digit(remainder, divisor);
+            quotient = quotient.checked_mul(10)?.checked_add(digit)?;
+            remainder = next;
+        }
+        let mut places = digits + a.scale - b.scale;
+        //a trailing 1 stands for the digits which are left, so that an inexact half is not rounded as a tie
+        if remainder != 0 {
+            quotient = quotient.checked_mul(10)?.checked_add(1)?;
+            places += 1;
+        }
+        let mantissa = i128::try_from(quotient).ok()?;
+        let negative = a.is_negative() != b.is_negative();
+        Some(
+            Decimal {
+                mantissa: if negative { -mantissa } else { mantissa },
+                scale: places,
+            }
+            .round(scale, RoundingMode::HalfEven),
+        )
+    }
+}
+
+///Returns the next digit of the long division by `divisor` and the new remainder, i.e. `remainder * 10 /
+/// divisor` and `remainder * 10 % divisor` without overflowing for divisors close to `u128::MAX`
+fn next_digit(remainder: u128, divisor: u128) -> (u128, u128) {
+    let (mut digit, mut next) = (0, 0);
+    for _ in 0..10 {
+        //both summands are smaller than the divisor, so next + remainder is compared without computing it
+        if next >= divisor - remainder {
+            next -= divisor - remainder;
+            digit += 1;
+        } else {
+            next += remainder;
+        }
+    }
+    (digit, next)
+}
+
+impl Add for Decimal {
+    type Output = Decimal;
+    /// # Panics
+    /// If the integer part of the sum does not fit into 38 digits
+    fn add(self, other: Decimal) -> Decimal {
+        match self.checked_add(other) {
+            Some(sum) => sum,
+            None => panic!("decimal overflow in {} + {}", self, other),
+        }
+    }
+}
+
+impl Sub for Decimal {
+    type Output = Decimal;
+    /// # Panics
+    /// If the integer part of the difference does not fit into 38 digits
+    fn sub(self, other: Decimal) -> Decimal {
+        self + -other
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Decimal;
+    fn neg(self) -> Decimal {
+        Decimal {
+            mantissa: -self.mantissa,
+            scale: self.scale,
+        }
+    }
+}
+
+impl Mul for Decimal {
+    type Output = Decimal;
+    ///Returns the product rounded to `MAX_SCALE` decimal places
+    /// # Panics
+    /// If the integer part of the product does not fit into 38 digits
+    fn mul(self, other: Decimal) -> Decimal {
+        match self.checked_mul(other) {
+            Some(product) => product,
+            None => panic!("decimal overflow in {} * {}", self, other),
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Decimal {}
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let (a, b) = (self.normalize(), other.normalize());
+        let scale = a.scale.max(b.scale);
+        match (a.mantissa_at(scale), b.mantissa_at(scale)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            //one of them is too large to be scaled, so the integer parts differ
+            _ => a.to_f64().total_cmp(&b.to_f64()),
+        }
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+    ///Parses numbers like "49898.19", "-0.5", "1e-7" or "2.5E3". Places after `MAX_SCALE` are rounded half to
+    /// even.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseDecimalError {
+            text: String::from(s),
+        };
+        let text = s.trim();
+        let (text, exponent) = match text.find(['e', 'E']) {
+            Some(i) => match text[i + 1..].parse::<i32>() {
+                Ok(e) => (&text[..i], e),
+                Err(_) => return Err(error()),
+            },
+            None => (text, 0),
+        };
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(t) => (true, t),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = match text.find('.') {
+            Some(i) => (&text[..i], &text[i + 1..]),
+            None => (text, ""),
+        };
+        if (integer.is_empty() && fraction.is_empty())
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(error());
+        }
+        let mut digits = format!("{}{}", integer, fraction);
+        let mut scale = fraction.len() as i64 - exponent as i64;
+        //digits after the place deciding the rounding only matter if they are not all zero
+        let keep = MAX_SCALE as i64 + 1;
+        if scale > keep {
+            let excess = (scale - keep) as usize;
+            if excess >= digits.len() {
+                return Ok(Decimal::ZERO);
+            }
+            let dropped = digits.split_off(digits.len() - excess);
+            scale = keep;
+            if dropped.chars().any(|c| c != '0') {
+                digits.push('1');
+                scale += 1;
+            }
+        }
+        let mut mantissa: i128 = 0;
+        for c in digits.trim_start_matches('0').chars() {
+            let digit = c.to_digit(10).unwrap_or(0) as i128;
+            mantissa = match mantissa.checked_mul(10).and_then(|m| m.checked_add(digit)) {
+                Some(m) => m,
+                None => return Err(error()),
+            };
+        }
+        if scale < 0 {
+            mantissa = match 10i128
+                .checked_pow((-scale) as u32)
+                .and_then(|p| p.checked_mul(mantissa))
+            {
+                Some(m) => m,
+                None => return Err(error()),
+            };
+            scale = 0;
+        }
+        Ok(Decimal::new(
+            if negative { -mantissa } else { mantissa },
+            scale as u32,
+        ))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let sign = if self.is_negative() { "-" } else { "" };
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+struct DecimalVisitor;
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = Decimal;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a decimal number or a string holding one")
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Decimal, E> {
+        v.parse().map_err(E::custom)
+    }
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Decimal, E> {
+        Decimal::from_f64(v).ok_or_else(|| E::custom(format!("{} is not a decimal number", v)))
+    }
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Decimal, E> {
+        Ok(Decimal::new(v as i128, 0))
+    }
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Decimal, E> {
+        Ok(Decimal::new(v as i128, 0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decimal, RoundingMode, MAX_SCALE};
+
+    fn d(text: &str) -> Decimal {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for (text, shown) in [
+            ("49898.19", "49898.19"),
+            ("-0.5", "-0.5"),
+            (".25", "0.25"),
+            ("0.00000812", "0.00000812"),
+            ("1e-7", "0.0000001"),
+            ("2.5E3", "2500"),
+            ("4301554902", "4301554902"),
+            ("1.50", "1.50"),
+            ("0.1234567890123456789", "0.123456789012345679"),
+        ]
+        .iter()
+        {
+            assert_eq!(d(text).to_string(), *shown);
+        }
+        for text in ["", ".", "1.2.3", "12a", "1e", "--1"].iter() {
+            assert!(text.parse::<Decimal>().is_err(), "{}", text);
+        }
+        assert_eq!(d("1.50"), d("1.5"));
+        assert!(d("0.1") < d("0.10000001"));
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+        assert_eq!(d("49898.19").to_f64(), 49898.19);
+    }
+
+    #[test]
+    fn test_round() {
+        let round = |text: &str, mode| d(text).round(1, mode).to_string();
+        assert_eq!(round("2.25", RoundingMode::HalfEven), "2.2");
+        assert_eq!(round("2.35", RoundingMode::HalfEven), "2.4");
+        assert_eq!(round("2.251", RoundingMode::HalfEven), "2.3");
+        assert_eq!(round("2.25", RoundingMode::HalfUp), "2.3");
+        assert_eq!(round("-2.25", RoundingMode::HalfUp), "-2.3");
+        assert_eq!(round("2.25", RoundingMode::HalfDown), "2.2");
+        assert_eq!(round("2.29", RoundingMode::Down), "2.2");
+        assert_eq!(round("2.21", RoundingMode::Up), "2.3");
+        assert_eq!(round("-2.21", RoundingMode::Floor), "-2.3");
+        assert_eq!(round("-2.29", RoundingMode::Ceiling), "-2.2");
+        assert_eq!(round("2", RoundingMode::Up), "2");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(d("0.1") * d("3"), d("0.3"));
+        assert_eq!((d("49898.19") * d("0.5")).to_string(), "24949.095");
+        assert_eq!(d("1").checked_div(d("3"), 4).unwrap().to_string(), "0.3333");
+        assert_eq!(d("-2").checked_div(d("3"), 2).unwrap().to_string(), "-0.67");
+        assert_eq!(d("0.125").checked_div(d("1"), 2).unwrap(), d("0.12"));
+        assert!(d("1").checked_div(Decimal::ZERO, 2).is_none());
+        assert_eq!(d("0.5").checked_div(d("-4"), 2).unwrap(), d("-0.12"));
+        assert_eq!(d("0.50001").checked_div(d("4"), 2).unwrap(), d("0.13"));
+        assert_eq!(d("1234.5").checked_div(d("0.01"), 0).unwrap(), d("123450"));
+        //operands with many places must not overflow while scaling
+        assert_eq!(
+            d("885140978.410182972141331222")
+                .checked_div(d("44811.592376164180338759"), MAX_SCALE)
+                .unwrap(),
+            d("19752.5")
+        );
+        let max = Decimal::new(i128::MAX, 0);
+        assert_eq!(max.checked_div(max, MAX_SCALE).unwrap(), Decimal::ONE);
+        assert_eq!(d("1").checked_div(max, 2).unwrap(), Decimal::ZERO);
+        assert!(max.checked_div(d("0.1"), 0).is_none());
+        //sums of many small prices do not drift like floating point numbers
+        let cent = d("0.01");
+        let mut total = Decimal::ZERO;
+        for _ in 0..1000 {
+            total = total + cent;
+        }
+        assert_eq!(total, d("10"));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::fx;
+use crate::html::ParseError;
+use crate::money::Decimal;
+use crate::{MarketResult, PriceResult};
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde_json::Value;
+
+///Selector of the script tag holding the data the CoinMarketCap page is rendered from
+const NEXT_DATA_SELECTOR: &str = "script#__NEXT_DATA__";
+///Location of the coin information inside of the `__NEXT_DATA__` JSON
+const INFO: &str = "/props/initialProps/pageProps/info";
+///Location of the market pairs inside of the `__NEXT_DATA__` JSON
+const MARKET_PAIRS: &str = "/props/initialProps/pageProps/marketPairs";
+///Location of the latest quotes of the coins on the page, keyed by their CoinMarketCap id
+const QUOTES: &str = "/props/initialState/cryptocurrency/quotesLatest/data";
+
+///Returns the `__NEXT_DATA__` JSON embedded in the CoinMarketCap page `html`
+/// # Errors
+/// If the page does not contain the script tag or its content is not valid JSON an error is returned
+pub fn get_next_data(html: &str) -> Result<Value, ParseError> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse(NEXT_DATA_SELECTOR).unwrap();
+    let script = match document.select(&selector).next() {
+        Some(s) => s,
+        None => {
+            return Err(ParseError::ElementNotFound {
+                pattern: String::from(NEXT_DATA_SELECTOR),
+            })
+        }
+    };
+    match serde_json::from_str(&script.inner_html()) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(ParseError::Json {
+            details: e.to_string(),
+        }),
+    }
+}
+
+///Returns the value stored at the JSON `pointer` inside of `data`
+fn get<'a>(data: &'a Value, pointer: &str) -> Result<&'a Value, ParseError> {
+    match data.pointer(pointer) {
+        Some(v) if !v.is_null() => Ok(v),
+        _ => Err(ParseError::ElementNotFound {
+            pattern: String::from(pointer),
+        }),
+    }
+}
+
+///Returns the number stored at the JSON `pointer` inside of `data`
+fn get_number(data: &Value, pointer: &str) -> Result<f64, ParseError> {
+    let value = get(data, pointer)?;
+    match value.as_f64() {
+        Some(n) => Ok(n),
+        None => Err(ParseError::NumberParse {
+            text: value.to_string(),
+        }),
+    }
+}
+
+///Returns the number stored at the JSON `pointer` inside of `data` as `Decimal`. The number is converted from
+/// its text in the JSON, which `serde_json` keeps with the `arbitrary_precision` feature, not from a floating
+/// point value.
+fn get_decimal(data: &Value, pointer: &str) -> Result<Decimal, ParseError> {
+    let value = get(data, pointer)?;
+    let text = match value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    };
+    match text.parse() {
+        Ok(d) => Ok(d),
+        Err(_) => Err(ParseError::NumberParse { text }),
+    }
+}
+
+///Returns the string stored at the JSON `pointer` inside of `data`
+fn get_string(data: &Value, pointer: &str) -> Result<String, ParseError> {
+    match get(data, pointer)?.as_str() {
+        Some(s) => Ok(String::from(s)),
+        None => Err(ParseError::ElementNotFound {
+            pattern: String::from(pointer),
+        }),
+    }
+}
+
+///Extracts the price, the 24h change and the statistics of `symbol` from the `__NEXT_DATA__` JSON of a currency
+/// page. The 1h and 7d changes are taken from the USD quote of the coin, see `parse_changes`. Statistics which
+/// are missing in the JSON are left out.
+/// # Arguments
+/// * `html` - The source of the currency page
+/// * `symbol` - The symbol for which the price is extracted
+pub fn parse_price(html: &str, symbol: String) -> Result<PriceResult, ParseError> {
+    let data = get_next_data(html)?;
+    let statistic = |name: &str| format!("{}/statistics/{}", INFO, name);
+    let (change_1h, change_7d) = quote_changes(&data);
+    Ok(PriceResult {
+        symbol,
+        price: get_decimal(&data, &statistic("price"))?,
+        change: get_number(&data, &statistic("priceChangePercentage24h"))?,
+        currency: String::from(fx::USD),
+        market_cap: get_decimal(&data, &statistic("marketCap")).ok(),
+        fully_diluted_market_cap: get_decimal(&data, &statistic("fullyDilutedMarketCap")).ok(),
+        volume_24h: get_decimal(&data, &format!("{}/volume", INFO)).ok(),
+        rank: get(&data, &statistic("rank"))
+            .ok()
+            .and_then(|r| r.as_u64())
+            .map(|r| r as u32),
+        circulating_supply: get_decimal(&data, &statistic("circulatingSupply")).ok(),
+        total_supply: get_decimal(&data, &statistic("totalSupply")).ok(),
+        max_supply: get_decimal(&data, &statistic("maxSupply")).ok(),
+        change_1h,
+        change_7d,
+    })
+}
+
+///Returns the changes of the price within 1 hour and 7 days in percent from the `__NEXT_DATA__` JSON of a
+/// currency page. They are not shown in the markup; they are only part of the USD quote of the coin, which is
+/// looked up by the id of the coin. Changes which are missing are None.
+pub fn parse_changes(html: &str) -> (Option<f64>, Option<f64>) {
+    match get_next_data(html) {
+        Ok(data) => quote_changes(&data),
+        Err(_) => (None, None),
+    }
+}
+
+///Returns the 1h and 7d changes of the USD quote of the coin described in `data`
+fn quote_changes(data: &Value) -> (Option<f64>, Option<f64>) {
+    let quote = match get(data, &format!("{}/id", INFO)) {
+        Ok(id) => format!("{}/{}/quote/USD", QUOTES, id),
+        Err(_) => return (None, None),
+    };
+    (
+        get_number(data, &format!("{}/percent_change_1h", quote)).ok(),
+        get_number(data, &format!("{}/percent_change_7d", quote)).ok(),
+    )
+}
+
+///Extracts the first `number_of_results` markets from the `__NEXT_DATA__` JSON of a markets page
+/// # Arguments
+/// * `html` - The source of the markets page
+/// * `number_of_results`- Number of markets which has to be returned
+pub fn parse_markets(html: &str, number_of_results: i32) -> Result<Vec<MarketResult>, ParseError> {
+    let data = get_next_data(html)?;
+    let total_volume = get_number(&data, &format!("{}/volume", INFO))?;
+    let mut result = Vec::new();
+    for i in 0..number_of_results {
+        let pair = format!("{}/{}", MARKET_PAIRS, i);
+        let volume = get_decimal(&data, &format!("{}/quote/USD/volume_24h", pair))?;
+        result.push(MarketResult {
+            source: get_string(&data, &format!("{}/exchange/name", pair))?,
+            pair: get_string(&data, &format!("{}/market_pair", pair))?,
+            price: get_decimal(&data, &format!("{}/quote/USD/price", pair))?,
+            volume,
+            volume_percent: if total_volume == 0.0 {
+                0.0
+            } else {
+                volume.to_f64() / total_volume * 100.0
+            },
+            currency: String::from(fx::USD),
+        });
+    }
+    Ok(result)
+}
+
+///Extracts the description of the currency ("What is" section) from the `__NEXT_DATA__` JSON of a currency page.
+/// The description is stored as markdown which is converted to the same plain text layout `get_details` returns.
+pub fn parse_details(html: &str) -> Result<String, ParseError> {
+    let data = get_next_data(html)?;
+    let mut text = get_string(&data, &format!("{}/description", INFO))?;
+    //titles like "## **What Is Bitcoin (BTC)?**"
+    let title = Regex::new(r"(?m)^#+\s*(?:\*\*)?(.*?)(?:\*\*)?\s*$").unwrap();
+    text = title
+        .replace_all(&text, "\n------------$1------------\n")
+        .into_owned();
+    //links like "[whitepaper](https://...)"
+    let link = Regex::new(r"\[(.*?)\]\(.*?\)").unwrap();
+    text = link.replace_all(&text, "$1").into_owned();
+    Ok(text.replace("**", ""))
+}
@@ -0,0 +1,230 @@
+use crate::config::{Rounding, RoundingRule};
+use crate::{MarketResult, PriceResult};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+///The formats in which prices and markets can be rendered
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    ///The human readable text printed by the interactive menu
+    #[default]
+    Table,
+    ///A single JSON array
+    Json,
+    ///One JSON object per line
+    Ndjson,
+    ///Comma separated values with a header line
+    Csv,
+    ///Tab separated values with a header line
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!(
+                "Unknown format \"{}\". Use table, json, ndjson, csv or tsv.",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+///Returns the rule of `rounding` which applies to `format`
+pub fn rounding_rule(rounding: Option<&Rounding>, format: OutputFormat) -> Option<&RoundingRule> {
+    let rounding = rounding?;
+    match format {
+        OutputFormat::Table => rounding.table.as_ref(),
+        OutputFormat::Json | OutputFormat::Ndjson => rounding.json.as_ref(),
+        OutputFormat::Csv | OutputFormat::Tsv => rounding.csv.as_ref(),
+    }
+}
+
+///Renders `records` in one of the machine readable formats. `Table` has no generic layout; it is rendered by
+/// the callers and falls back to CSV here.
+fn render<T: Serialize>(records: &[T], format: OutputFormat) -> String {
+    //serializing flat records into memory can not fail
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(records).unwrap() + "\n",
+        OutputFormat::Ndjson => records.iter().fold(String::new(), |mut text, r| {
+            text.push_str(&serde_json::to_string(r).unwrap());
+            text.push('\n');
+            text
+        }),
+        OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::Table => {
+            let delimiter = if format == OutputFormat::Tsv {
+                b'\t'
+            } else {
+                b','
+            };
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(Vec::new());
+            for r in records {
+                writer.serialize(r).unwrap();
+            }
+            String::from_utf8(writer.into_inner().unwrap()).unwrap()
+        }
+    }
+}
+
+///Renders `prices` in `format`. The table format prints one `symbol: price currency (change)` line per price.
+/// # Arguments
+/// * `prices` - The prices to be rendered
+/// * `format` - The format of the returned text
+/// * `rounding` - The rounding of the outputs, see `rounding` in the config
+pub fn render_prices(
+    prices: &[&PriceResult],
+    format: OutputFormat,
+    rounding: Option<&Rounding>,
+) -> String {
+    let rule = rounding_rule(rounding, format);
+    let prices: Vec<PriceResult> = prices.iter().map(|p| p.rounded(rule)).collect();
+    match format {
+        OutputFormat::Table => prices
+            .iter()
+            .fold(String::new(), |text, p| format!("{}{}\n", text, p)),
+        _ => render(&prices, format),
+    }
+}
+
+///Renders `markets` in `format`. The table format prints the header returned by `MarketResult::get_header`
+/// followed by one line per market.
+/// # Arguments
+/// * `markets` - The markets to be rendered
+/// * `format` - The format of the returned text
+/// * `rounding` - The rounding of the outputs, see `rounding` in the config
+pub fn render_markets(
+    markets: &[MarketResult],
+    format: OutputFormat,
+    rounding: Option<&Rounding>,
+) -> String {
+    let rule = rounding_rule(rounding, format);
+    let markets: Vec<MarketResult> = markets.iter().map(|m| m.rounded(rule)).collect();
+    match format {
+        OutputFormat::Table => markets
+            .iter()
+            .fold(format!("{}\n", MarketResult::get_header()), |text, m| {
+                format!("{}{}\n", text, m)
+            }),
+        _ => render(&markets, format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_markets, render_prices, OutputFormat};
+    use crate::config::RoundingMode;
+    use crate::config::{Rounding, RoundingRule};
+    use crate::{MarketResult, PriceResult};
+
+    const CSV_HEADER: &str =
+        "symbol,price,change,currency,market_cap,fully_diluted_market_cap,volume_24h,rank,\
+        circulating_supply,total_supply,max_supply,change_1h,change_7d";
+
+    fn price() -> PriceResult {
+        PriceResult {
+            symbol: String::from("bitcoin"),
+            price: "49898.19".parse().unwrap(),
+            change: -1.48,
+            currency: String::from("USD"),
+            market_cap: Some("930332625053".parse().unwrap()),
+            rank: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_prices() {
+        let price = price();
+        let prices = vec![&price];
+        assert_eq!(
+            render_prices(&prices, OutputFormat::Table, None),
+            "bitcoin: 49898.19 USD (-1.48)\n"
+        );
+        assert_eq!(
+            render_prices(&prices, OutputFormat::Ndjson, None),
+            concat!(
+                "{\"symbol\":\"bitcoin\",\"price\":\"49898.19\",\"change\":-1.48,\"currency\":\"USD\",",
+                "\"market_cap\":\"930332625053\",\"fully_diluted_market_cap\":null,\"volume_24h\":null,",
+                "\"rank\":1,\"circulating_supply\":null,\"total_supply\":null,\"max_supply\":null,",
+                "\"change_1h\":null,\"change_7d\":null}\n"
+            )
+        );
+        assert_eq!(
+            render_prices(&prices, OutputFormat::Csv, None),
+            format!(
+                "{}\nbitcoin,49898.19,-1.48,USD,930332625053,,,1,,,,,\n",
+                CSV_HEADER
+            )
+        );
+        assert_eq!(
+            render_prices(&prices, OutputFormat::Tsv, None),
+            format!(
+                "{}\nbitcoin\t49898.19\t-1.48\tUSD\t930332625053\t\t\t1\t\t\t\t\t\n",
+                CSV_HEADER.replace(",", "\t")
+            )
+        );
+        let parsed: Vec<PriceResult> =
+            serde_json::from_str(&render_prices(&prices, OutputFormat::Json, None)).unwrap();
+        assert_eq!(parsed.iter().collect::<Vec<_>>(), prices);
+        let rounding = Rounding {
+            csv: Some(RoundingRule {
+                places: 1,
+                mode: RoundingMode::Down,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            render_prices(&prices, OutputFormat::Csv, Some(&rounding)),
+            format!(
+                "{}\nbitcoin,49898.1,-1.48,USD,930332625053,,,1,,,,,\n",
+                CSV_HEADER
+            )
+        );
+        assert_eq!(
+            render_prices(&prices, OutputFormat::Table, Some(&rounding)),
+            "bitcoin: 49898.19 USD (-1.48)\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markets_long_names() {
+        let markets = vec![MarketResult {
+            source: String::from("Binance, the exchange with a long name"),
+            pair: String::from("BTC/USDT"),
+            price: "49765.123456789".parse().unwrap(),
+            volume: "4301554902.0".parse().unwrap(),
+            volume_percent: 6.08,
+            currency: String::from("USD"),
+        }];
+        let table = render_markets(&markets, OutputFormat::Table, None);
+        assert!(table.contains("Binance, the exchange with a long name BTC/USDT"));
+        assert_eq!(
+            render_markets(&markets, OutputFormat::Csv, None),
+            "source,pair,price,volume,volume_percent,currency\n\"Binance, the exchange with a long name\",BTC/USDT,49765.123456789,4301554902.0,6.08,USD\n"
+        );
+        assert_eq!("tsv".parse::<OutputFormat>(), Ok(OutputFormat::Tsv));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::daemon::shutdown_signal;
+use crate::html::ParseError;
+use crate::CoinMarketCapScrapper;
+use serde::Serialize;
+use serde_json::json;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+///Number of markets returned by `/markets/{symbol}` without `limit`
+const DEFAULT_MARKET_LIMIT: i32 = 3;
+
+///Errors which stop the API server
+#[derive(Debug)]
+pub enum ServerError {
+    ///The server could not listen on `address`
+    Bind { address: String, details: String },
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Bind { address, details } => {
+                write!(f, "Could not listen on {}: {}", address, details)
+            }
+        }
+    }
+}
+
+impl Error for ServerError {}
+
+///The answer of the API to a request. The body is JSON for all endpoints except `/metrics`.
+#[derive(Debug, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl ApiResponse {
+    fn json<T: Serialize>(status: u16, value: &T) -> ApiResponse {
+        ApiResponse {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string(value).unwrap(),
+        }
+    }
+    fn error(status: u16, message: &str) -> ApiResponse {
+        ApiResponse::json(status, &json!({ "error": message }))
+    }
+    ///Returns the error response of a failed scrape. A symbol CoinMarketCap does not know is answered with
+    /// 404, a temporary failure with 503 and every other error with 502.
+    fn scrape_error(error: &ParseError) -> ApiResponse {
+        let status = match error {
+            ParseError::Transport {
+                status: Some(404), ..
+            } => 404,
+            e if e.is_transient() => 503,
+            _ => 502,
+        };
+        ApiResponse::error(status, &error.to_string())
+    }
+}
+
+///Returns the value of the parameter `name` of the query string `query`, e.g. "5" for `limit` in "limit=5"
+fn query_parameter<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| {
+        let mut parts = pair.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if key == name => Some(value),
+            _ => None,
+        }
+    })
+}
+
+///Answers a request of the API with `scrapper`. The endpoints are
+/// * `GET /prices[?symbols=a,b]` - the prices of the given symbols or of all symbols in the config
+/// * `GET /prices/{symbol}` - the price of a single symbol
+/// * `GET /markets/{symbol}[?limit=N]` - the markets with the highest volume of a symbol
+/// * `GET /details/{symbol}` - the "What is" section of a symbol
+/// * `GET /metrics` - the metrics of the scrapper in the Prometheus text format
+/// * `GET /symbols`, `POST /symbols` with `{"symbol": "..."}` and `DELETE /symbols/{symbol}` - the symbols
+///   stored in the config
+/// # Arguments
+/// * `method` - The HTTP method of the request, e.g. "GET"
+/// * `url` - The path of the request including the query string
+/// * `body` - The body of the request
+pub fn handle(
+    scrapper: &mut CoinMarketCapScrapper,
+    method: &str,
+    url: &str,
+    body: &str,
+) -> ApiResponse {
+    let mut parts = url.splitn(2, '?');
+    let path = parts.next().unwrap_or("");
+    let query = parts.next().unwrap_or("");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let rounding = scrapper.cfg.configuration.rounding.clone();
+    let rule = rounding.as_ref().and_then(|r| r.json.as_ref());
+    match (method, segments.as_slice()) {
+        ("GET", ["prices"]) => {
+            let batch = match query_parameter(query, "symbols") {
+                Some(symbols) => {
+                    let symbols: Vec<String> = symbols
+                        .split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect();
+                    scrapper.get_prices(&symbols)
+                }
+                None => scrapper.get_all_prices(),
+            };
+            let errors: Vec<_> = batch
+                .errors()
+                .map(|(s, e)| json!({ "symbol": s, "error": e.to_string() }))
+                .collect();
+            let prices: Vec<_> = batch.prices().map(|p| p.rounded(rule)).collect();
+            ApiResponse::json(200, &json!({ "prices": prices, "errors": errors }))
+        }
+        ("GET", ["prices", symbol]) => match scrapper.get_price(symbol) {
+            Ok(p) => ApiResponse::json(200, &p.rounded(rule)),
+            Err(e) => ApiResponse::scrape_error(&e),
+        },
+        ("GET", ["markets", symbol]) => {
+            let limit = match query_parameter(query, "limit") {
+                Some(l) => match l.parse::<i32>() {
+                    Ok(l) if l > 0 => l,
+                    _ => return ApiResponse::error(400, "limit has to be a positive number"),
+                },
+                None => DEFAULT_MARKET_LIMIT,
+            };
+            match scrapper.get_market_data(symbol, limit) {
+                Ok(m) => {
+                    let markets: Vec<_> = m.iter().map(|m| m.rounded(rule)).collect();
+                    ApiResponse::json(200, &markets)
+                }
+                Err(e) => ApiResponse::scrape_error(&e),
+            }
+        }
+        ("GET", ["details", symbol]) => match scrapper.get_details(symbol) {
+            Ok(d) => ApiResponse::json(200, &json!({ "symbol": symbol, "details": d })),
+            Err(e) => ApiResponse::scrape_error(&e),
+        },
+        ("GET", ["metrics"]) => ApiResponse {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body: scrapper.render_metrics(),
+        },
+        ("GET", ["symbols"]) => ApiResponse::json(200, &scrapper.cfg.get_symbols()),
+        ("POST", ["symbols"]) => {
+            let symbol = match serde_json::from_str::<serde_json::Value>(body) {
+                Ok(v) => match v.get("symbol").and_then(|s| s.as_str()) {
+                    Some(s) if !s.is_empty() => String::from(s),
+                    _ => return ApiResponse::error(400, "The body needs a \"symbol\""),
+                },
+                Err(e) => return ApiResponse::error(400, &e.to_string()),
+            };
+            if scrapper.cfg.get_symbols().contains(&symbol) {
+                return ApiResponse::error(409, &format!("{} is already in the config", symbol));
+            }
+            scrapper.cfg.add_symbol(symbol);
+            match scrapper.cfg.store() {
+                Ok(_) => ApiResponse::json(201, &scrapper.cfg.get_symbols()),
+                Err(e) => ApiResponse::error(500, &e.to_string()),
+            }
+        }
+        ("DELETE", ["symbols", symbol]) => {
+            match scrapper.cfg.get_symbols().iter().position(|s| s == symbol) {
+                Some(i) => {
+                    scrapper.cfg.delete_symbol(i);
+                    match scrapper.cfg.store() {
+                        Ok(_) => ApiResponse::json(200, &scrapper.cfg.get_symbols()),
+                        Err(e) => ApiResponse::error(500, &e.to_string()),
+                    }
+                }
+                None => ApiResponse::error(404, &format!("{} is not in the config", symbol)),
+            }
+        }
+        (_, ["prices"])
+        | (_, ["prices", _])
+        | (_, ["markets", _])
+        | (_, ["details", _])
+        | (_, ["metrics"])
+        | (_, ["symbols"])
+        | (_, ["symbols", _]) => ApiResponse::error(405, "Method not allowed"),
+        _ => ApiResponse::error(404, "Not found"),
+    }
+}
+
+///Answers the requests of the API on `address` (e.g. "127.0.0.1:8080") with `scrapper` until SIGINT or
+/// SIGTERM is received. All requests share the page cache of `scrapper` and are answered one after another.
+/// # Errors
+/// If the server can not listen on `address` an error is returned
+pub fn serve(scrapper: &mut CoinMarketCapScrapper, address: &str) -> Result<(), ServerError> {
+    let server = match tiny_http::Server::http(address) {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            return Err(ServerError::Bind {
+                address: String::from(address),
+                details: e.to_string(),
+            })
+        }
+    };
+    println!("Listening on http://{}", server.server_addr());
+    let unblock = Arc::clone(&server);
+    scrapper.runtime.spawn(async move {
+        if shutdown_signal().await.is_ok() {
+            unblock.unblock();
+        }
+    });
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => handle(
+                scrapper,
+                request.method().as_str(),
+                &String::from(request.url()),
+                &body,
+            ),
+            Err(e) => ApiResponse::error(400, &e.to_string()),
+        };
+        let content_type =
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], response.content_type.as_bytes())
+                .unwrap();
+        let response = tiny_http::Response::from_string(response.body)
+            .with_status_code(response.status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            eprintln!("Could not answer a request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::handle;
+    use crate::html::FileFetcher;
+    use crate::CoinMarketCapScrapper;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_endpoints() {
+        let config = std::env::temp_dir().join("crypto_scrapper_server.toml");
+        std::fs::copy("./config/config.toml", &config).unwrap();
+        let fetcher = FileFetcher::new("./").with_page(
+            "https://coinmarketcap.com/currencies/bitcoin/",
+            "bitcoin.html",
+        );
+        let mut scrapper = CoinMarketCapScrapper::with_fetcher(
+            String::from(config.to_str().unwrap()),
+            Arc::new(fetcher),
+        )
+        .unwrap();
+        let response = handle(&mut scrapper, "GET", "/prices/bitcoin", "");
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.body,
+            concat!(
+                r#"{"symbol":"bitcoin","price":"49898.19","change":-1.48,"currency":"USD","#,
+                r#""market_cap":"930332625053","fully_diluted_market_cap":"1047861915224","#,
+                r#""volume_24h":"50103411504","rank":1,"circulating_supply":"18644618","#,
+                r#""total_supply":"18644618","max_supply":"21000000","change_1h":0.88219637,"change_7d":-1.71749491}"#
+            )
+        );
+        let response = handle(&mut scrapper, "GET", "/prices?symbols=bitcoin,nocoin", "");
+        assert_eq!(response.status, 200);
+        let value: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(value["prices"].as_array().unwrap().len(), 1);
+        assert_eq!(value["errors"][0]["symbol"], "nocoin");
+        assert_eq!(
+            handle(&mut scrapper, "GET", "/markets/bitcoin?limit=x", "").status,
+            400
+        );
+        assert_eq!(handle(&mut scrapper, "PUT", "/symbols", "").status, 405);
+        let metrics = handle(&mut scrapper, "GET", "/metrics", "");
+        assert!(metrics.content_type.starts_with("text/plain"));
+        assert!(metrics
+            .body
+            .contains("crypto_scrapper_price{symbol=\"bitcoin\",currency=\"USD\"} 49898.19\n"));
+        assert_eq!(handle(&mut scrapper, "GET", "/nothing", "").status, 404);
+
+        let added = handle(
+            &mut scrapper,
+            "POST",
+            "/symbols",
+            r#"{"symbol": "dogecoin"}"#,
+        );
+        assert_eq!(added.status, 201);
+        assert!(added.body.contains("\"dogecoin\""));
+        assert_eq!(
+            handle(
+                &mut scrapper,
+                "POST",
+                "/symbols",
+                r#"{"symbol": "dogecoin"}"#
+            )
+            .status,
+            409
+        );
+        assert!(std::fs::read_to_string(&config)
+            .unwrap()
+            .contains("\"dogecoin\""));
+        assert_eq!(
+            handle(&mut scrapper, "DELETE", "/symbols/dogecoin", "").status,
+            200
+        );
+        assert_eq!(
+            handle(&mut scrapper, "DELETE", "/symbols/dogecoin", "").status,
+            404
+        );
+    }
+}
@@ -0,0 +1,847 @@
+use crate::PriceResult;
+use chrono::{DateTime, Local};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+///Column of the symbols. Rows are matched by the value of this column.
+const SYMBOL_COLUMN: u32 = 1;
+///Column the price is written to
+const PRICE_COLUMN: u32 = 2;
+///Column the 24h change is written to
+const CHANGE_COLUMN: u32 = 3;
+///Column the time the prices were fetched is written to
+const UPDATED_COLUMN: u32 = 4;
+///Column the currency of the price is written to, so prices in different currencies are never mistaken for each
+/// other after the currency of the config changed
+const CURRENCY_COLUMN: u32 = 5;
+///Header written into the first row of an empty sheet
+const HEADER: [&str; 5] = ["Symbol", "Price", "24h change", "Updated", "Currency"];
+
+const WORKBOOK: &str = "xl/workbook.xml";
+const WORKBOOK_RELS: &str = "xl/_rels/workbook.xml.rels";
+
+///Errors which can occur while updating a workbook
+#[derive(Debug)]
+pub enum SpreadsheetError {
+    ///The workbook could not be read or written
+    Io { path: String, details: String },
+    ///The workbook is not a valid xlsx (zip) file
+    Zip { details: String },
+    ///A part of the workbook is not valid XML or misses an expected element
+    Xml { part: String, details: String },
+    ///The workbook has no sheet called `sheet`
+    SheetNotFound { sheet: String },
+    ///The cell `cell` which has to be updated holds a formula. Formulas are never overwritten.
+    Formula { cell: String },
+}
+
+impl fmt::Display for SpreadsheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpreadsheetError::Io { path, details } => {
+                write!(f, "Could not access workbook {}: {}", path, details)
+            }
+            SpreadsheetError::Zip { details } => {
+                write!(f, "The workbook is not a valid xlsx file: {}", details)
+            }
+            SpreadsheetError::Xml { part, details } => {
+                write!(f, "Could not read {} of the workbook: {}", part, details)
+            }
+            SpreadsheetError::SheetNotFound { sheet } => {
+                write!(f, "The workbook has no sheet called \"{}\"", sheet)
+            }
+            SpreadsheetError::Formula { cell } => {
+                write!(f, "Cell {} holds a formula and is not overwritten", cell)
+            }
+        }
+    }
+}
+
+impl Error for SpreadsheetError {}
+
+impl From<zip::result::ZipError> for SpreadsheetError {
+    fn from(e: zip::result::ZipError) -> Self {
+        SpreadsheetError::Zip {
+            details: e.to_string(),
+        }
+    }
+}
+
+///Returns the XML error of `part`
+fn xml_error(part: &str, details: impl fmt::Display) -> SpreadsheetError {
+    SpreadsheetError::Xml {
+        part: String::from(part),
+        details: details.to_string(),
+    }
+}
+
+///Writes `prices` into the sheet `sheet` of the workbook stored at `path`.
+///
+/// The symbols are matched against column A of the sheet. Price, 24h change, `fetched` and the currency of the
+/// price are written to columns B, C, D and E of the matching row; symbols which are not in the sheet yet are appended as new rows.
+/// All other cells, sheets, styles and formulas of the workbook are left untouched. If the workbook does not
+/// exist a new one with a single sheet called `sheet` is created.
+/// # Arguments
+/// * `path` - Location of the .xlsx file
+/// * `sheet` - Name of the sheet holding the prices
+/// * `prices` - The prices to be written
+/// * `fetched` - The time the prices were fetched
+/// # Errors
+/// If the workbook can not be read or written, has no sheet called `sheet` or a cell which has to be updated
+/// holds a formula, an error is returned and the workbook is not changed.
+pub fn update_workbook(
+    path: &str,
+    sheet: &str,
+    prices: &[&PriceResult],
+    fetched: SystemTime,
+) -> Result<(), SpreadsheetError> {
+    let io_error = |e: std::io::Error| SpreadsheetError::Io {
+        path: String::from(path),
+        details: e.to_string(),
+    };
+    let workbook = if Path::new(path).exists() {
+        std::fs::read(path).map_err(io_error)?
+    } else {
+        new_workbook(sheet)?
+    };
+    let updated = update_workbook_bytes(workbook, sheet, prices, fetched)?;
+    //write to a temporary file first so a failing write never leaves a broken workbook behind
+    let temp = format!("{}.tmp", path);
+    std::fs::write(&temp, updated).map_err(io_error)?;
+    std::fs::rename(&temp, path).map_err(io_error)
+}
+
+///Updates the workbook stored in `workbook` and returns the new content of the file
+fn update_workbook_bytes(
+    workbook: Vec<u8>,
+    sheet: &str,
+    prices: &[&PriceResult],
+    fetched: SystemTime,
+) -> Result<Vec<u8>, SpreadsheetError> {
+    let mut archive = ZipArchive::new(Cursor::new(workbook))?;
+    let workbook_xml = read_part(&mut archive, WORKBOOK)?;
+    let relations = read_part(&mut archive, WORKBOOK_RELS)?;
+    let sheet_part = find_sheet(&workbook_xml, &relations, sheet)?;
+    let shared_strings = match find_relation(&relations, "/sharedStrings")? {
+        Some(part) => parse_shared_strings(&read_part(&mut archive, &part)?, &part)?,
+        None => Vec::new(),
+    };
+    let sheet_xml = read_part(&mut archive, &sheet_part)?;
+    let timestamp = DateTime::<Local>::from(fetched)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let sheet_xml = update_sheet(&sheet_xml, &sheet_part, &shared_strings, prices, &timestamp)?;
+    //formulas depending on the prices are recalculated when the workbook is opened
+    let workbook_xml = if workbook_xml.contains("fullCalcOnLoad") {
+        workbook_xml
+    } else {
+        workbook_xml.replacen("<calcPr", "<calcPr fullCalcOnLoad=\"1\"", 1)
+    };
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = String::from(file.name());
+        if name == sheet_part {
+            writer.start_file(name, options)?;
+            write_part(&mut writer, &sheet_xml)?;
+        } else if name == WORKBOOK {
+            writer.start_file(name, options)?;
+            write_part(&mut writer, &workbook_xml)?;
+        } else {
+            writer.raw_copy_file(file)?;
+        }
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+///Returns the content of the part `name` of the workbook
+fn read_part(
+    archive: &mut ZipArchive<Cursor<Vec<u8>>>,
+    name: &str,
+) -> Result<String, SpreadsheetError> {
+    let mut content = String::new();
+    match archive.by_name(name)?.read_to_string(&mut content) {
+        Ok(_) => Ok(content),
+        Err(e) => Err(xml_error(name, e)),
+    }
+}
+
+///Writes `content` into the part which was started last in `writer`
+fn write_part(
+    writer: &mut ZipWriter<Cursor<Vec<u8>>>,
+    content: &str,
+) -> Result<(), SpreadsheetError> {
+    match writer.write_all(content.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(SpreadsheetError::Zip {
+            details: e.to_string(),
+        }),
+    }
+}
+
+///Returns the value of the attribute `key` of the element `e`, unescaped
+fn attribute(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes().flatten().find(|a| a.key == key).map(|a| {
+        let value = a.unescaped_value().unwrap_or(a.value.clone());
+        String::from_utf8_lossy(&value).into_owned()
+    })
+}
+
+///Resolves a relationship target of the workbook to the name of the part in the zip file
+fn part_name(target: &str) -> String {
+    match target.strip_prefix('/') {
+        Some(absolute) => String::from(absolute),
+        None => format!("xl/{}", target),
+    }
+}
+
+///Returns the part of the worksheet called `sheet`
+fn find_sheet(workbook: &str, relations: &str, sheet: &str) -> Result<String, SpreadsheetError> {
+    let mut reader = Reader::from_str(workbook);
+    let mut buf = Vec::new();
+    let mut id = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Empty(e)) | Ok(Event::Start(e))
+                if e.local_name() == b"sheet"
+                    && attribute(&e, b"name").as_deref() == Some(sheet) =>
+            {
+                id = attribute(&e, b"r:id");
+                break;
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(xml_error(WORKBOOK, e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    let id = match id {
+        Some(id) => id,
+        None => {
+            return Err(SpreadsheetError::SheetNotFound {
+                sheet: String::from(sheet),
+            })
+        }
+    };
+    let mut reader = Reader::from_str(relations);
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Empty(e)) | Ok(Event::Start(e))
+                if e.local_name() == b"Relationship"
+                    && attribute(&e, b"Id").as_deref() == Some(&id) =>
+            {
+                if let Some(target) = attribute(&e, b"Target") {
+                    return Ok(part_name(&target));
+                }
+            }
+            Ok(Event::Eof) => {
+                return Err(xml_error(WORKBOOK_RELS, format!("no relationship {}", id)))
+            }
+            Err(e) => return Err(xml_error(WORKBOOK_RELS, e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+///Returns the part of the first relationship whose type ends with `type_suffix`
+fn find_relation(relations: &str, type_suffix: &str) -> Result<Option<String>, SpreadsheetError> {
+    let mut reader = Reader::from_str(relations);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Empty(e)) | Ok(Event::Start(e)) if e.local_name() == b"Relationship" => {
+                let matches = attribute(&e, b"Type")
+                    .map(|t| t.ends_with(type_suffix))
+                    .unwrap_or(false);
+                if matches {
+                    return Ok(attribute(&e, b"Target").map(|t| part_name(&t)));
+                }
+            }
+            Ok(Event::Eof) => return Ok(None),
+            Err(e) => return Err(xml_error(WORKBOOK_RELS, e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+///Returns the texts of the shared string table. Cells of type "s" hold an index into this table.
+fn parse_shared_strings(xml: &str, part: &str) -> Result<Vec<String>, SpreadsheetError> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut strings = Vec::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(e)) if e.local_name() == b"si" => strings.push(String::new()),
+            Ok(Event::Start(e)) if e.local_name() == b"t" => in_text = true,
+            Ok(Event::End(e)) if e.local_name() == b"t" => in_text = false,
+            Ok(Event::Text(e)) if in_text => {
+                let text = e.unescaped().map_err(|e| xml_error(part, e))?;
+                if let Some(s) = strings.last_mut() {
+                    s.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Ok(Event::Eof) => return Ok(strings),
+            Err(e) => return Err(xml_error(part, e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+///A cell of a worksheet. The XML of the cell is kept as it is, unless the cell is updated.
+struct Cell {
+    column: u32,
+    style: Option<String>,
+    formula: bool,
+    xml: String,
+}
+
+///A row of a worksheet
+struct Row {
+    number: u32,
+    attributes: Vec<(String, String)>,
+    cells: Vec<Cell>,
+    modified: bool,
+}
+
+impl Row {
+    fn new(number: u32) -> Row {
+        Row {
+            number,
+            attributes: vec![(String::from("r"), number.to_string())],
+            cells: Vec::new(),
+            modified: true,
+        }
+    }
+    ///Returns the cell in `column`
+    fn cell(&self, column: u32) -> Option<&Cell> {
+        self.cells.iter().find(|c| c.column == column)
+    }
+    ///Sets the value of the cell in `column` to `value`. The style of an existing cell is kept.
+    /// `value` is written as number if `number` is true, otherwise as text.
+    fn set(&mut self, column: u32, value: &str, number: bool) -> Result<(), SpreadsheetError> {
+        let reference = format!("{}{}", column_name(column), self.number);
+        let style = match self.cell(column) {
+            Some(c) if c.formula => return Err(SpreadsheetError::Formula { cell: reference }),
+            Some(c) => c.style.clone(),
+            None => None,
+        };
+        let style_attribute = match &style {
+            Some(s) => format!(" s=\"{}\"", s),
+            None => String::new(),
+        };
+        let xml = if number {
+            format!(
+                "<c r=\"{}\"{}><v>{}</v></c>",
+                reference, style_attribute, value
+            )
+        } else {
+            format!(
+                "<c r=\"{}\"{} t=\"inlineStr\"><is><t>{}</t></is></c>",
+                reference,
+                style_attribute,
+                escape(value)
+            )
+        };
+        let cell = Cell {
+            column,
+            style,
+            formula: false,
+            xml,
+        };
+        match self.cells.iter().position(|c| c.column >= column) {
+            Some(i) if self.cells[i].column == column => self.cells[i] = cell,
+            Some(i) => self.cells.insert(i, cell),
+            None => self.cells.push(cell),
+        }
+        self.modified = true;
+        Ok(())
+    }
+    fn to_xml(&self) -> String {
+        let mut xml = String::from("<row");
+        for (key, value) in &self.attributes {
+            //the column span is only a hint and might be wrong once cells are added
+            if !(self.modified && key == "spans") {
+                xml.push_str(&format!(" {}=\"{}\"", key, value));
+            }
+        }
+        xml.push('>');
+        for c in &self.cells {
+            xml.push_str(&c.xml);
+        }
+        xml.push_str("</row>");
+        xml
+    }
+}
+
+///Escapes the characters of `text` which are not allowed in XML text
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+///Returns the name of the 1-based `column`, e.g. "A" for 1 and "AA" for 27
+fn column_name(column: u32) -> String {
+    let mut name = Vec::new();
+    let mut column = column;
+    while column > 0 {
+        let remainder = (column - 1) % 26;
+        name.push((b'A' + remainder as u8) as char);
+        column = (column - 1) / 26;
+    }
+    name.iter().rev().collect()
+}
+
+///Returns the 1-based column of the cell `reference`, e.g. 2 for "B12"
+fn column_number(reference: &str) -> u32 {
+    reference
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .fold(0, |n, c| {
+            n * 26 + (c.to_ascii_uppercase() as u32 - 'A' as u32 + 1)
+        })
+}
+
+///Returns the raw attributes of the element `e` as they are written in the XML
+fn raw_attributes(e: &BytesStart) -> Vec<(String, String)> {
+    e.attributes()
+        .flatten()
+        .map(|a| {
+            (
+                String::from_utf8_lossy(a.key).into_owned(),
+                String::from_utf8_lossy(&a.value).into_owned(),
+            )
+        })
+        .collect()
+}
+
+///Parses the rows of the `sheetData` element `xml`
+fn parse_rows(xml: &str, part: &str) -> Result<Vec<Row>, SpreadsheetError> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut rows: Vec<Row> = Vec::new();
+    let mut row: Option<Row> = None;
+    let mut cell: Option<(usize, Cell)> = None;
+    let mut position = 0;
+    loop {
+        let event = reader.read_event(&mut buf);
+        let end = reader.buffer_position();
+        match event {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name() == b"row" => {
+                let number = match attribute(&e, b"r").and_then(|r| r.parse().ok()) {
+                    Some(n) => n,
+                    None => rows.last().map(|r| r.number + 1).unwrap_or(1),
+                };
+                let new_row = Row {
+                    number,
+                    attributes: raw_attributes(&e),
+                    cells: Vec::new(),
+                    modified: false,
+                };
+                if xml[position..end].ends_with("/>") {
+                    rows.push(new_row);
+                } else {
+                    row = Some(new_row);
+                }
+            }
+            Ok(Event::End(e)) if e.local_name() == b"row" => {
+                if let Some(r) = row.take() {
+                    rows.push(r);
+                }
+            }
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name() == b"c" => {
+                let previous = row
+                    .as_ref()
+                    .and_then(|r| r.cells.last())
+                    .map(|c| c.column)
+                    .unwrap_or(0);
+                let new_cell = Cell {
+                    column: match attribute(&e, b"r") {
+                        Some(r) => column_number(&r),
+                        None => previous + 1,
+                    },
+                    style: attribute(&e, b"s"),
+                    formula: false,
+                    xml: String::new(),
+                };
+                if xml[position..end].ends_with("/>") {
+                    if let Some(r) = row.as_mut() {
+                        r.cells.push(Cell {
+                            xml: String::from(&xml[position..end]),
+                            ..new_cell
+                        });
+                    }
+                } else {
+                    cell = Some((position, new_cell));
+                }
+            }
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name() == b"f" => {
+                if let Some((_, c)) = cell.as_mut() {
+                    c.formula = true;
+                }
+            }
+            Ok(Event::End(e)) if e.local_name() == b"c" => {
+                if let (Some((start, c)), Some(r)) = (cell.take(), row.as_mut()) {
+                    r.cells.push(Cell {
+                        xml: String::from(&xml[start..end]),
+                        ..c
+                    });
+                }
+            }
+            Ok(Event::Eof) => return Ok(rows),
+            Err(e) => return Err(xml_error(part, e)),
+            _ => {}
+        }
+        position = end;
+        buf.clear();
+    }
+}
+
+///Returns the text shown in the cell `xml`
+fn cell_text(xml: &str, shared_strings: &[String]) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut cell_type = None;
+    let mut text = String::new();
+    let mut in_value = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(e)) if e.local_name() == b"c" => cell_type = attribute(&e, b"t"),
+            Ok(Event::Start(e)) if e.local_name() == b"v" || e.local_name() == b"t" => {
+                in_value = true
+            }
+            Ok(Event::End(e)) if e.local_name() == b"v" || e.local_name() == b"t" => {
+                in_value = false
+            }
+            Ok(Event::Text(e)) if in_value => {
+                text.push_str(&String::from_utf8_lossy(&e.unescaped().ok()?));
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    match cell_type.as_deref() {
+        Some("s") => shared_strings
+            .get(text.trim().parse::<usize>().ok()?)
+            .cloned(),
+        _ => Some(text),
+    }
+}
+
+///Writes `prices` into the worksheet `xml` and returns the updated worksheet
+fn update_sheet(
+    xml: &str,
+    part: &str,
+    shared_strings: &[String],
+    prices: &[&PriceResult],
+    timestamp: &str,
+) -> Result<String, SpreadsheetError> {
+    //everything but the sheetData element is kept as it is
+    let (before, data, after) = match (xml.find("<sheetData"), xml.find("</sheetData>")) {
+        (Some(start), Some(end)) => match xml[start..end].find('>') {
+            Some(i) => (&xml[..start], &xml[start + i + 1..end], &xml[end + 12..]),
+            None => return Err(xml_error(part, "unterminated sheetData element")),
+        },
+        //an empty sheet has a self-closing `<sheetData/>`
+        (Some(start), None) => match xml[start..].find('>') {
+            Some(i) if xml[..start + i].ends_with('/') => {
+                (&xml[..start], "", &xml[start + i + 1..])
+            }
+            _ => return Err(xml_error(part, "unterminated sheetData element")),
+        },
+        _ => return Err(xml_error(part, "no sheetData element")),
+    };
+    let mut rows = parse_rows(data, part)?;
+    if rows.is_empty() {
+        let mut header = Row::new(1);
+        for (i, title) in HEADER.iter().enumerate() {
+            header.set(i as u32 + 1, title, false)?;
+        }
+        rows.push(header);
+    }
+    let mut symbol_rows: HashMap<String, usize> = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        if let Some(symbol) = row
+            .cell(SYMBOL_COLUMN)
+            .and_then(|c| cell_text(&c.xml, shared_strings))
+        {
+            symbol_rows.entry(String::from(symbol.trim())).or_insert(i);
+        }
+    }
+    for price in prices {
+        let i = match symbol_rows.get(&price.symbol) {
+            Some(i) => *i,
+            None => {
+                let number = rows.iter().map(|r| r.number).max().unwrap_or(0) + 1;
+                let mut row = Row::new(number);
+                row.set(SYMBOL_COLUMN, &price.symbol, false)?;
+                rows.push(row);
+                symbol_rows.insert(price.symbol.clone(), rows.len() - 1);
+                rows.len() - 1
+            }
+        };
+        let row = &mut rows[i];
+        row.set(PRICE_COLUMN, &price.price.to_string(), true)?;
+        row.set(CHANGE_COLUMN, &price.change.to_string(), true)?;
+        row.set(UPDATED_COLUMN, timestamp, false)?;
+        row.set(CURRENCY_COLUMN, &price.currency, false)?;
+    }
+    let last_row = rows.iter().map(|r| r.number).max().unwrap_or(1);
+    let last_column = rows
+        .iter()
+        .flat_map(|r| r.cells.iter().map(|c| c.column))
+        .max()
+        .unwrap_or(1);
+    let dimension = format!(
+        "<dimension ref=\"A1:{}{}\"/>",
+        column_name(last_column),
+        last_row
+    );
+    let before = match before.find("<dimension ") {
+        Some(start) => {
+            let end = start + before[start..].find("/>").unwrap_or(0) + 2;
+            format!("{}{}{}", &before[..start], dimension, &before[end..])
+        }
+        None => String::from(before),
+    };
+    let data: String = rows.iter().map(|r| r.to_xml()).collect();
+    Ok(format!(
+        "{}<sheetData>{}</sheetData>{}",
+        before, data, after
+    ))
+}
+
+///Returns a new workbook containing the single empty sheet `sheet`
+fn new_workbook(sheet: &str) -> Result<Vec<u8>, SpreadsheetError> {
+    let parts = [
+        (
+            "[Content_Types].xml",
+            String::from(concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+                r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+                r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+                r#"<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>"#,
+                r#"<Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+                r#"</Types>"#
+            )),
+        ),
+        (
+            "_rels/.rels",
+            String::from(concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+                r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>"#,
+                r#"</Relationships>"#
+            )),
+        ),
+        (
+            WORKBOOK,
+            format!(
+                concat!(
+                    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                    r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" "#,
+                    r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">"#,
+                    r#"<sheets><sheet name="{}" sheetId="1" r:id="rId1"/></sheets><calcPr/></workbook>"#
+                ),
+                escape(sheet)
+            ),
+        ),
+        (
+            WORKBOOK_RELS,
+            String::from(concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+                r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>"#,
+                r#"</Relationships>"#
+            )),
+        ),
+        (
+            "xl/worksheets/sheet1.xml",
+            String::from(concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">"#,
+                r#"<dimension ref="A1"/><sheetData/></worksheet>"#
+            )),
+        ),
+    ];
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, content) in parts.iter() {
+        writer.start_file(*name, options)?;
+        write_part(&mut writer, content)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        column_name, column_number, new_workbook, read_part, update_sheet, update_workbook_bytes,
+        SpreadsheetError,
+    };
+    use crate::money::Decimal;
+    use crate::PriceResult;
+    use std::io::{Cursor, Write};
+    use std::time::SystemTime;
+    use zip::write::FileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    fn price(symbol: &str, price: f64, change: f64) -> PriceResult {
+        PriceResult {
+            symbol: String::from(symbol),
+            price: Decimal::from_f64(price).unwrap(),
+            change,
+            currency: String::from("USD"),
+            ..Default::default()
+        }
+    }
+
+    fn sheet(workbook: Vec<u8>, part: &str) -> String {
+        let mut archive = ZipArchive::new(Cursor::new(workbook)).unwrap();
+        read_part(&mut archive, part).unwrap()
+    }
+
+    #[test]
+    fn test_column_names() {
+        assert_eq!(column_name(1), "A");
+        assert_eq!(column_name(27), "AA");
+        assert_eq!(column_number("AB12"), 28);
+        assert_eq!(column_number("d4"), 4);
+    }
+
+    #[test]
+    fn test_malformed_sheet() {
+        let bitcoin = price("bitcoin", 49898.19, -1.48);
+        for xml in [
+            "<worksheet><sheetData",
+            "<worksheet><sheetData></worksheet>",
+            "<worksheet><sheetData</sheetData></worksheet>",
+            "<worksheet></worksheet>",
+        ]
+        .iter()
+        {
+            match update_sheet(xml, "sheet1.xml", &[], &[&bitcoin], "now") {
+                Err(SpreadsheetError::Xml { part, .. }) => assert_eq!(part, "sheet1.xml"),
+                r => panic!("expected an xml error for {}, got {:?}", xml, r),
+            }
+        }
+        let xml = update_sheet(
+            "<worksheet><sheetData/></worksheet>",
+            "sheet1.xml",
+            &[],
+            &[&bitcoin],
+            "now",
+        )
+        .unwrap();
+        assert!(xml.contains("<t>bitcoin</t>"));
+    }
+
+    #[test]
+    fn test_new_workbook() {
+        let bitcoin = price("bitcoin", 49898.19, -1.48);
+        let workbook = new_workbook("Prices").unwrap();
+        let workbook =
+            update_workbook_bytes(workbook, "Prices", &[&bitcoin], SystemTime::now()).unwrap();
+        let xml = sheet(workbook, "xl/worksheets/sheet1.xml");
+        assert!(xml.contains("<dimension ref=\"A1:E2\"/>"));
+        assert!(xml.contains("<t>Symbol</t>"));
+        assert!(xml.contains("<t>Currency</t>"));
+        assert!(xml.contains(
+            "<row r=\"2\"><c r=\"A2\" t=\"inlineStr\"><is><t>bitcoin</t></is></c><c r=\"B2\"><v>49898.19</v></c><c r=\"C2\"><v>-1.48</v></c>"
+        ));
+        assert!(xml.contains("<c r=\"E2\" t=\"inlineStr\"><is><t>USD</t></is></c></row>"));
+        assert!(update_workbook_bytes(
+            new_workbook("Prices").unwrap(),
+            "Other",
+            &[],
+            SystemTime::now()
+        )
+        .is_err());
+    }
+
+    ///Returns a workbook made of `parts`
+    fn workbook(parts: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in parts {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_update_in_place() {
+        //a sheet with shared strings, styles, an unrelated column and a formula outside of the price columns
+        let styles = "<styleSheet><numFmts count=\"1\"/></styleSheet>";
+        let original = workbook(&[
+            (
+                "xl/workbook.xml",
+                r#"<workbook><sheets><sheet name="Summary" sheetId="1" r:id="rId3"/><sheet name="Prices" sheetId="2" r:id="rId1"/></sheets><calcPr calcId="191029"/></workbook>"#,
+            ),
+            (
+                "xl/_rels/workbook.xml.rels",
+                r#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet2.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/sharedStrings" Target="/xl/sharedStrings.xml"/></Relationships>"#,
+            ),
+            (
+                "xl/worksheets/sheet2.xml",
+                concat!(
+                    r#"<worksheet><dimension ref="A3:G4"/><sheetData>"#,
+                    r#"<row r="3" spans="1:7"><c r="A3" t="s"><v>0</v></c><c r="B3" s="4"><v>1</v></c><c r="G3"><v>0.5</v></c></row>"#,
+                    r#"<row r="4"><c r="A4" t="s"><v>1</v></c><c r="F4"><f>B3*G3</f><v>0.5</v></c></row>"#,
+                    r#"</sheetData><pageMargins left="0.7"/></worksheet>"#
+                ),
+            ),
+            (
+                "xl/sharedStrings.xml",
+                "<sst><si><t>bitcoin</t></si><si><r><t>ether</t></r><r><t>eum</t></r></si></sst>",
+            ),
+            ("xl/styles.xml", styles),
+        ]);
+        let bitcoin = price("bitcoin", 49898.19, -1.48);
+        let ethereum = price("ethereum", 1500.5, 2.0);
+        let dai = price("multi-collateral-dai", 1.0, 0.01);
+        let updated = update_workbook_bytes(
+            original,
+            "Prices",
+            &[&bitcoin, &ethereum, &dai],
+            SystemTime::now(),
+        )
+        .unwrap();
+        let xml = sheet(updated.clone(), "xl/worksheets/sheet2.xml");
+        //matched through the shared strings, style and unrelated cells are kept
+        assert!(xml.contains(
+            r#"<row r="3"><c r="A3" t="s"><v>0</v></c><c r="B3" s="4"><v>49898.19</v></c><c r="C3"><v>-1.48</v></c>"#
+        ));
+        assert!(xml.contains(
+            r#"<c r="E3" t="inlineStr"><is><t>USD</t></is></c><c r="G3"><v>0.5</v></c></row>"#
+        ));
+        assert!(xml.contains(r#"<c r="B4"><v>1500.5</v></c>"#));
+        assert!(xml.contains(r#"<c r="F4"><f>B3*G3</f><v>0.5</v></c></row>"#));
+        //new symbols are appended, the rest of the sheet and the workbook is left as it is
+        assert!(xml.contains(
+            r#"<row r="5"><c r="A5" t="inlineStr"><is><t>multi-collateral-dai</t></is></c>"#
+        ));
+        assert!(xml.starts_with(r#"<worksheet><dimension ref="A1:G5"/><sheetData>"#));
+        assert!(xml.ends_with("</sheetData><pageMargins left=\"0.7\"/></worksheet>"));
+        assert!(!xml.contains("<t>Symbol</t>"));
+        assert_eq!(sheet(updated.clone(), "xl/styles.xml"), styles);
+        assert!(sheet(updated, "xl/workbook.xml").contains("<calcPr fullCalcOnLoad=\"1\" calcId"));
+    }
+}
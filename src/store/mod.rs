@@ -0,0 +1,433 @@
+use crate::money::Decimal;
+use crate::{MarketResult, PriceResult};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, Row, ToSql};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+///Tables of the store. Prices and volumes are stored as decimal text, a REAL column would convert them to
+/// floating point numbers.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS prices (
+    id INTEGER PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    price TEXT NOT NULL,
+    change REAL NOT NULL,
+    currency TEXT NOT NULL,
+    market_cap TEXT,
+    fully_diluted_market_cap TEXT,
+    volume_24h TEXT,
+    rank INTEGER,
+    circulating_supply TEXT,
+    total_supply TEXT,
+    max_supply TEXT,
+    change_1h REAL,
+    change_7d REAL
+);
+CREATE INDEX IF NOT EXISTS prices_symbol_timestamp ON prices (symbol, timestamp);
+CREATE TABLE IF NOT EXISTS markets (
+    id INTEGER PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    market_source TEXT NOT NULL,
+    pair TEXT NOT NULL,
+    price TEXT NOT NULL,
+    volume TEXT NOT NULL,
+    volume_percent REAL NOT NULL,
+    currency TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS markets_symbol_timestamp ON markets (symbol, timestamp);
+";
+///Changes of `SCHEMA` applied to databases whose `user_version` is lower than the index of the change plus one
+const MIGRATIONS: [&str; 0] = [];
+
+///Errors of the time-series store
+#[derive(Debug)]
+pub enum StoreError {
+    ///No store is configured, see `store` in the config
+    NotConfigured,
+    ///The database could not be opened, read or written
+    Sqlite { details: String },
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::NotConfigured => write!(f, "No store is configured"),
+            StoreError::Sqlite { details } => write!(f, "Store error: {}", details),
+        }
+    }
+}
+
+impl Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite {
+            details: e.to_string(),
+        }
+    }
+}
+
+///Decimals are stored as text to keep all of their digits
+impl ToSql for Decimal {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl FromSql for Decimal {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Text(text) => match std::str::from_utf8(text) {
+                Ok(t) => t.parse().map_err(|e| FromSqlError::Other(Box::new(e))),
+                Err(e) => Err(FromSqlError::Other(Box::new(e))),
+            },
+            ValueRef::Integer(i) => Ok(Decimal::new(i as i128, 0)),
+            ValueRef::Real(r) => Decimal::from_f64(r).ok_or(FromSqlError::InvalidType),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+///A `PriceResult` recorded at `timestamp`
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PriceSnapshot {
+    pub timestamp: SystemTime,
+    ///Where the price was taken from, e.g. "coinmarketcap.com/markup"
+    pub source: String,
+    pub price: PriceResult,
+}
+
+///A `MarketResult` of `symbol` recorded at `timestamp`
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MarketSnapshot {
+    pub timestamp: SystemTime,
+    ///Where the market was taken from, e.g. "coinmarketcap.com/markup"
+    pub source: String,
+    pub symbol: String,
+    pub market: MarketResult,
+}
+
+///Returns `time` as milliseconds since the unix epoch, the way timestamps are stored
+fn to_millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+///Returns the `SystemTime` of the stored milliseconds since the unix epoch
+fn from_millis(millis: i64) -> SystemTime {
+    if millis >= 0 {
+        UNIX_EPOCH + Duration::from_millis(millis as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_millis(millis.unsigned_abs())
+    }
+}
+
+///Maps a row of the prices table to a `PriceSnapshot`
+fn price_snapshot(row: &Row) -> rusqlite::Result<PriceSnapshot> {
+    Ok(PriceSnapshot {
+        timestamp: from_millis(row.get(1)?),
+        source: row.get(2)?,
+        price: PriceResult {
+            symbol: row.get(0)?,
+            price: row.get(3)?,
+            change: row.get(4)?,
+            currency: row.get(5)?,
+            market_cap: row.get(6)?,
+            fully_diluted_market_cap: row.get(7)?,
+            volume_24h: row.get(8)?,
+            rank: row.get(9)?,
+            circulating_supply: row.get(10)?,
+            total_supply: row.get(11)?,
+            max_supply: row.get(12)?,
+            change_1h: row.get(13)?,
+            change_7d: row.get(14)?,
+        },
+    })
+}
+
+///Maps a row of the markets table to a `MarketSnapshot`
+fn market_snapshot(row: &Row) -> rusqlite::Result<MarketSnapshot> {
+    Ok(MarketSnapshot {
+        symbol: row.get(0)?,
+        timestamp: from_millis(row.get(1)?),
+        source: row.get(2)?,
+        market: MarketResult {
+            source: row.get(3)?,
+            pair: row.get(4)?,
+            price: row.get(5)?,
+            volume: row.get(6)?,
+            volume_percent: row.get(7)?,
+            currency: row.get(8)?,
+        },
+    })
+}
+
+const PRICE_COLUMNS: &str = "SELECT symbol, timestamp, source, price, change, currency, market_cap, fully_diluted_market_cap,
+     volume_24h, rank, circulating_supply, total_supply, max_supply, change_1h, change_7d FROM prices";
+const MARKET_COLUMNS: &str =
+    "SELECT symbol, timestamp, source, market_source, pair, price, volume, volume_percent, currency FROM markets";
+
+///Embedded SQLite store recording every scraped price and market with the time it was scraped
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    ///Opens the store in the SQLite database at `path`. The database and its tables are created if they do not
+    /// exist yet, the tables of an older version are migrated.
+    /// # Errors
+    /// If the database can not be opened or created an error is returned
+    pub fn open(path: &str) -> Result<Store, StoreError> {
+        Store::with_connection(Connection::open(path)?)
+    }
+    ///Opens a store which is only kept in memory
+    pub fn open_in_memory() -> Result<Store, StoreError> {
+        Store::with_connection(Connection::open_in_memory()?)
+    }
+    fn with_connection(connection: Connection) -> Result<Store, StoreError> {
+        connection.execute_batch(SCHEMA)?;
+        let version: i64 = connection.query_row("PRAGMA user_version", params![], |r| r.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let transaction = connection.unchecked_transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", &(i as i64 + 1))?;
+            transaction.commit()?;
+        }
+        Ok(Store { connection })
+    }
+
+    ///Records `prices` as scraped from `source` at `timestamp`
+    pub fn insert_prices(
+        &self,
+        prices: &[&PriceResult],
+        source: &str,
+        timestamp: SystemTime,
+    ) -> Result<(), StoreError> {
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO prices (symbol, timestamp, source, price, change, currency, market_cap,
+                     fully_diluted_market_cap, volume_24h, rank, circulating_supply, total_supply, max_supply,
+                     change_1h, change_7d)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for p in prices {
+                statement.execute(params![
+                    p.symbol,
+                    to_millis(timestamp),
+                    source,
+                    p.price,
+                    p.change,
+                    p.currency,
+                    p.market_cap,
+                    p.fully_diluted_market_cap,
+                    p.volume_24h,
+                    p.rank,
+                    p.circulating_supply,
+                    p.total_supply,
+                    p.max_supply,
+                    p.change_1h,
+                    p.change_7d
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    ///Records the `markets` of `symbol` as scraped from `source` at `timestamp`
+    pub fn insert_markets(
+        &self,
+        symbol: &str,
+        markets: &[MarketResult],
+        source: &str,
+        timestamp: SystemTime,
+    ) -> Result<(), StoreError> {
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO markets (symbol, timestamp, source, market_source, pair, price, volume, volume_percent, currency)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for m in markets {
+                statement.execute(params![
+                    symbol,
+                    to_millis(timestamp),
+                    source,
+                    m.source,
+                    m.pair,
+                    m.price,
+                    m.volume,
+                    m.volume_percent,
+                    m.currency
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    ///Returns the price of `symbol` at `time`, i.e. the last snapshot recorded at or before `time`
+    pub fn price_at(
+        &self,
+        symbol: &str,
+        time: SystemTime,
+    ) -> Result<Option<PriceSnapshot>, StoreError> {
+        let mut statement = self.connection.prepare_cached(&format!(
+            "{} WHERE symbol = ? AND timestamp <= ? ORDER BY timestamp DESC, id DESC LIMIT 1",
+            PRICE_COLUMNS
+        ))?;
+        let mut rows = statement.query_map(params![symbol, to_millis(time)], price_snapshot)?;
+        match rows.next() {
+            Some(snapshot) => Ok(Some(snapshot?)),
+            None => Ok(None),
+        }
+    }
+
+    ///Returns the prices of `symbol` recorded between `from` and `to` (both inclusive), oldest first
+    pub fn price_range(
+        &self,
+        symbol: &str,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<Vec<PriceSnapshot>, StoreError> {
+        let mut statement = self.connection.prepare_cached(&format!(
+            "{} WHERE symbol = ? AND timestamp BETWEEN ? AND ? ORDER BY timestamp, id",
+            PRICE_COLUMNS
+        ))?;
+        let rows = statement.query_map(
+            params![symbol, to_millis(from), to_millis(to)],
+            price_snapshot,
+        )?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    ///Returns the last `n` prices recorded for `symbol`, newest first
+    pub fn latest_prices(&self, symbol: &str, n: u32) -> Result<Vec<PriceSnapshot>, StoreError> {
+        let mut statement = self.connection.prepare_cached(&format!(
+            "{} WHERE symbol = ? ORDER BY timestamp DESC, id DESC LIMIT ?",
+            PRICE_COLUMNS
+        ))?;
+        let rows = statement.query_map(params![symbol, n], price_snapshot)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    ///Returns the markets of `symbol` recorded between `from` and `to` (both inclusive), oldest first
+    pub fn market_range(
+        &self,
+        symbol: &str,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<Vec<MarketSnapshot>, StoreError> {
+        let mut statement = self.connection.prepare_cached(&format!(
+            "{} WHERE symbol = ? AND timestamp BETWEEN ? AND ? ORDER BY timestamp, id",
+            MARKET_COLUMNS
+        ))?;
+        let rows = statement.query_map(
+            params![symbol, to_millis(from), to_millis(to)],
+            market_snapshot,
+        )?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Store;
+    use crate::money::Decimal;
+    use crate::{MarketResult, PriceResult};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn price(price: f64) -> PriceResult {
+        PriceResult {
+            symbol: String::from("bitcoin"),
+            price: Decimal::from_f64(price).unwrap(),
+            change: -1.48,
+            currency: String::from("USD"),
+            market_cap: Some(Decimal::new(930_332_625_053, 0)),
+            rank: Some(1),
+            change_7d: Some(-1.72),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_price_queries() {
+        let store = Store::open_in_memory().unwrap();
+        let t = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        for (i, p) in [49000.0, 49500.0, 50000.0].iter().enumerate() {
+            let p = price(*p);
+            store
+                .insert_prices(&[&p], "coinmarketcap.com/markup", t(100 * (i as u64 + 1)))
+                .unwrap();
+        }
+        assert!(store.price_at("bitcoin", t(50)).unwrap().is_none());
+        let at = store.price_at("bitcoin", t(250)).unwrap().unwrap();
+        assert_eq!(at.price, price(49500.0));
+        assert_eq!(at.timestamp, t(200));
+        assert_eq!(at.source, "coinmarketcap.com/markup");
+        let range = store.price_range("bitcoin", t(200), t(300)).unwrap();
+        let prices: Vec<f64> = range.iter().map(|s| s.price.price.to_f64()).collect();
+        assert_eq!(prices, vec![49500.0, 50000.0]);
+        let latest = store.latest_prices("bitcoin", 2).unwrap();
+        let prices: Vec<f64> = latest.iter().map(|s| s.price.price.to_f64()).collect();
+        assert_eq!(prices, vec![50000.0, 49500.0]);
+        assert!(store.latest_prices("ethereum", 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_market_queries() {
+        let store = Store::open_in_memory().unwrap();
+        let markets = vec![MarketResult {
+            source: String::from("Binance"),
+            pair: String::from("BTC/USDT"),
+            price: "0.000012345678901234".parse().unwrap(),
+            volume: "4301554902.10".parse().unwrap(),
+            volume_percent: 6.08,
+            currency: String::from("CHF"),
+        }];
+        let at = UNIX_EPOCH + Duration::from_secs(1_614_000_000);
+        store
+            .insert_markets("bitcoin", &markets, "coinmarketcap.com/markup", at)
+            .unwrap();
+        let range = store.market_range("bitcoin", at, at).unwrap();
+        assert_eq!(range.len(), 1);
+        assert_eq!(range[0].market, markets[0]);
+        assert_eq!(range[0].market.price.to_string(), "0.000012345678901234");
+        assert_eq!(range[0].symbol, "bitcoin");
+    }
+
+    #[test]
+    fn test_reopen_database() {
+        let path = std::env::temp_dir().join("crypto_scrapper_store_reopen.sqlite");
+        let _ = std::fs::remove_file(&path);
+        let price = PriceResult {
+            symbol: String::from("bitcoin"),
+            price: "0.1".parse().unwrap(),
+            change: 2.0,
+            currency: String::from("USD"),
+            ..Default::default()
+        };
+        let at = UNIX_EPOCH + Duration::from_secs(1_614_000_000);
+        Store::open(path.to_str().unwrap())
+            .unwrap()
+            .insert_prices(&[&price], "coinmarketcap.com/markup", at)
+            .unwrap();
+        //opening an existing database must keep its tables and rows
+        let store = Store::open(path.to_str().unwrap()).unwrap();
+        let latest = store.latest_prices("bitcoin", 1).unwrap();
+        assert_eq!(latest[0].price.price().to_string(), "0.1");
+        assert_eq!(latest[0].price.market_cap(), None);
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,239 @@
+//! Offline golden tests. Every case scrapes a saved page through a `FileFetcher` and compares the rendered
+//! result with the expected output stored next to the fixtures in `tests/fixtures/<case>.golden`.
+//!
+//! When a change of the parsing is intended, regenerate the expected outputs with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff of the `.golden` files.
+use crypto_scrapper::config::{Extractor, Rounding, RoundingMode, RoundingRule};
+use crypto_scrapper::fx;
+use crypto_scrapper::html::FileFetcher;
+use crypto_scrapper::output::{render_markets, render_prices, OutputFormat};
+use crypto_scrapper::CoinMarketCapScrapper;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const FIXTURES: &str = "./tests/fixtures";
+const CURRENCY_URL: &str = "https://coinmarketcap.com/currencies/bitcoin/";
+const MARKETS_URL: &str = "https://coinmarketcap.com/currencies/bitcoin/markets";
+
+///Returns a scrapper serving `currency_page` and `markets_page` for bitcoin and using `extractor`.
+/// The fixture config is copied to a temporary file first because the config is stored again when it is dropped.
+fn scrapper(
+    case: &str,
+    extractor: Extractor,
+    currency_page: &str,
+    markets_page: &str,
+) -> CoinMarketCapScrapper {
+    let config = std::env::temp_dir().join(format!("crypto_scrapper_golden_{}.toml", case));
+    std::fs::copy(Path::new(FIXTURES).join("config.toml"), &config).unwrap();
+    let fetcher = FileFetcher::new(FIXTURES)
+        .with_page(CURRENCY_URL, currency_page)
+        .with_page(MARKETS_URL, markets_page)
+        .with_page(fx::ECB_RATES_URL, "./tests/fixtures/eurofxref-daily.xml");
+    let mut scrapper = CoinMarketCapScrapper::with_fetcher(
+        config.to_str().unwrap().to_string(),
+        Arc::new(fetcher),
+    )
+    .unwrap();
+    scrapper.cfg.configuration.extractor = extractor;
+    scrapper
+}
+
+///Compares `actual` with the golden file of `case`, or rewrites the golden file if `UPDATE_GOLDEN` is set
+fn assert_golden(case: &str, actual: &str) {
+    let path: PathBuf = Path::new(FIXTURES).join(format!("{}.golden", case));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = match std::fs::read_to_string(&path) {
+        Ok(e) => e,
+        Err(e) => panic!(
+            "missing golden file {} ({}), run with UPDATE_GOLDEN=1 to create it",
+            path.display(),
+            e
+        ),
+    };
+    assert_eq!(
+        actual,
+        expected,
+        "output of {} differs from {}",
+        case,
+        path.display()
+    );
+}
+
+#[test]
+fn golden_price_markup() {
+    let mut scrapper = scrapper(
+        "price_markup",
+        Extractor::Markup,
+        "./bitcoin.html",
+        "./tests/fixtures/markets.html",
+    );
+    let price = scrapper.get_price("bitcoin").unwrap();
+    assert_golden("price_markup", &format!("{}\n", price));
+}
+
+#[test]
+fn golden_price_next_data() {
+    let mut scrapper = scrapper(
+        "price_next_data",
+        Extractor::NextData,
+        "./bitcoin.html",
+        "./tests/fixtures/markets.html",
+    );
+    let price = scrapper.get_price("bitcoin").unwrap();
+    assert_golden("price_next_data", &format!("{}\n", price));
+}
+
+#[test]
+fn golden_price_statistics() {
+    for (case, extractor) in [
+        ("price_statistics_markup", Extractor::Markup),
+        ("price_statistics_next_data", Extractor::NextData),
+    ]
+    .iter()
+    {
+        let mut scrapper = scrapper(
+            case,
+            *extractor,
+            "./bitcoin.html",
+            "./tests/fixtures/markets.html",
+        );
+        let price = scrapper.get_price("bitcoin").unwrap();
+        assert_golden(case, &render_prices(&[&price], OutputFormat::Json, None));
+    }
+}
+
+#[test]
+fn golden_price_selectors() {
+    let mut scrapper = scrapper(
+        "price_selectors",
+        Extractor::Selectors,
+        "./bitcoin.html",
+        "./tests/fixtures/markets.html",
+    );
+    let price = scrapper.get_price("bitcoin").unwrap();
+    assert_golden("price_selectors", &format!("{}\n", price));
+}
+
+#[test]
+fn golden_markets_markup() {
+    let mut scrapper = scrapper(
+        "markets_markup",
+        Extractor::Markup,
+        "./bitcoin.html",
+        "./tests/fixtures/markets.html",
+    );
+    let markets = scrapper.get_market_data("bitcoin", 3).unwrap();
+    assert_golden(
+        "markets_markup",
+        &render_markets(&markets, OutputFormat::Table, None),
+    );
+}
+
+#[test]
+fn golden_markets_selectors() {
+    let mut scrapper = scrapper(
+        "markets_selectors",
+        Extractor::Selectors,
+        "./bitcoin.html",
+        "./tests/fixtures/markets.html",
+    );
+    let markets = scrapper.get_market_data("bitcoin", 3).unwrap();
+    assert_golden(
+        "markets_selectors",
+        &render_markets(&markets, OutputFormat::Table, None),
+    );
+}
+
+#[test]
+fn golden_markets_next_data() {
+    let mut scrapper = scrapper(
+        "markets_next_data",
+        Extractor::NextData,
+        "./bitcoin.html",
+        "./bitcoin.html",
+    );
+    let markets = scrapper.get_market_data("bitcoin", 3).unwrap();
+    assert_golden(
+        "markets_next_data",
+        &render_markets(&markets, OutputFormat::Table, None),
+    );
+}
+
+#[test]
+fn golden_markets_next_data_csv() {
+    let mut scrapper = scrapper(
+        "markets_next_data_csv",
+        Extractor::NextData,
+        "./bitcoin.html",
+        "./bitcoin.html",
+    );
+    let markets = scrapper.get_market_data("bitcoin", 3).unwrap();
+    assert_golden(
+        "markets_next_data_csv",
+        &render_markets(&markets, OutputFormat::Csv, None),
+    );
+}
+
+#[test]
+fn golden_chf() {
+    let mut scrapper = scrapper(
+        "chf",
+        Extractor::Markup,
+        "./bitcoin.html",
+        "./tests/fixtures/markets.html",
+    );
+    scrapper.cfg.configuration.currency = Some(String::from("chf"));
+    let price = scrapper.get_price("bitcoin").unwrap();
+    assert_golden("price_chf", &format!("{}\n", price));
+    let markets = scrapper.get_market_data("bitcoin", 3).unwrap();
+    let rounding = Rounding {
+        csv: Some(RoundingRule {
+            places: 2,
+            mode: RoundingMode::HalfEven,
+        }),
+        ..Default::default()
+    };
+    assert_golden(
+        "markets_chf",
+        &render_markets(&markets, OutputFormat::Csv, Some(&rounding)),
+    );
+}
+
+#[test]
+fn golden_details_markup() {
+    let mut scrapper = scrapper(
+        "details_markup",
+        Extractor::Markup,
+        "./tests/fixtures/details.html",
+        "./tests/fixtures/markets.html",
+    );
+    let details = scrapper.get_details("bitcoin").unwrap();
+    assert_golden("details_markup", &details);
+}
+
+#[test]
+fn golden_details_selectors() {
+    let mut scrapper = scrapper(
+        "details_selectors",
+        Extractor::Selectors,
+        "./tests/fixtures/details.html",
+        "./tests/fixtures/markets.html",
+    );
+    let details = scrapper.get_details("bitcoin").unwrap();
+    assert_golden("details_selectors", &details);
+}
+
+#[test]
+fn golden_details_next_data() {
+    let mut scrapper = scrapper(
+        "details_next_data",
+        Extractor::NextData,
+        "./bitcoin.html",
+        "./tests/fixtures/markets.html",
+    );
+    let details = scrapper.get_details("bitcoin").unwrap();
+    assert_golden("details_next_data", &details);
+}